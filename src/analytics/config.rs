@@ -1,6 +1,24 @@
+use std::{collections::HashMap, path::PathBuf};
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Default)]
+fn default_channel_capacity() -> usize {
+    1024
+}
+
+fn default_max_buffered_events() -> usize {
+    50_000
+}
+
+fn default_spill_after_secs() -> u64 {
+    300
+}
+
+fn default_max_spilled_events() -> usize {
+    1_000_000
+}
+
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// URL of the clickhouse instance
@@ -12,4 +30,41 @@ pub struct Config {
     pub password: String,
     #[serde(default)]
     pub database: String,
+    /// How many events can be queued before new ones start getting dropped
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+    /// How many events are kept in memory while clickhouse is unreachable, oldest events are dropped first
+    #[serde(default = "default_max_buffered_events")]
+    pub max_buffered_events: usize,
+    /// Directory where events are written to if clickhouse is unreachable for a longer period of time.
+    /// They are replayed once the connection is back. Leave empty to disable.
+    #[serde(default)]
+    pub spill_directory: Option<PathBuf>,
+    /// How long (in seconds) writes must be failing before events start being spilled to disk
+    #[serde(default = "default_spill_after_secs")]
+    pub spill_after_secs: u64,
+    /// Retention window in days per event table, e.g. `login_events = 90`.
+    /// Tables that are not listed keep the TTL set by their migration, 0 disables the TTL entirely.
+    #[serde(default)]
+    pub retention_days: HashMap<String, u32>,
+    /// Maximum amount of events that can be stored on disk, further events are dropped
+    #[serde(default = "default_max_spilled_events")]
+    pub max_spilled_events: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            username: String::new(),
+            password: String::new(),
+            database: String::new(),
+            channel_capacity: default_channel_capacity(),
+            max_buffered_events: default_max_buffered_events(),
+            spill_directory: None,
+            spill_after_secs: default_spill_after_secs(),
+            retention_days: HashMap::new(),
+            max_spilled_events: default_max_spilled_events(),
+        }
+    }
 }
@@ -0,0 +1,11 @@
+CREATE TABLE moderation_events (
+    timestamp DateTime64(3) DEFAULT now(),
+    action LowCardinality(String),
+    issuer_id Int32,
+    -- 0 for actions without a single target, such as notices sent to a group of users
+    target_id Int32
+)
+ENGINE = MergeTree
+ORDER BY (timestamp, action, issuer_id)
+PARTITION BY toYYYYMM(timestamp)
+TTL timestamp + INTERVAL 365 DAY;
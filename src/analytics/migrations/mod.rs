@@ -1,8 +1,8 @@
 use anyhow::{Result, anyhow};
 use clickhouse::Client;
 use include_dir::{Dir, include_dir};
-use std::collections::HashSet;
-use tracing::{debug, info};
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, info, warn};
 
 // To add new migrations, simply create a new file in this directory, named similarly to the rest of the files
 static MIGRATIONS_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/analytics/migrations");
@@ -100,3 +100,78 @@ pub async fn run(client: &Client) -> Result<()> {
 
     Ok(())
 }
+
+/// Tables that support a configurable retention window, all of them have a `timestamp` column
+const EVENT_TABLES: &[&str] = &["login_events", "moderation_events"];
+
+/// Applies the retention windows from the config, overriding the TTL set by the migrations.
+/// Tables whose TTL already matches are left alone, failures are logged and don't stop the connection.
+pub async fn apply_retention(client: &Client, retention: &HashMap<String, u32>) {
+    for (table, &days) in retention {
+        if !EVENT_TABLES.contains(&table.as_str()) {
+            warn!("Unknown analytics table '{table}' in retention config, ignoring");
+            continue;
+        }
+
+        if let Err(e) = apply_table_retention(client, table, days).await {
+            warn!("Failed to set retention of '{table}' to {days} days: {e}");
+        }
+    }
+}
+
+async fn apply_table_retention(client: &Client, table: &str, days: u32) -> Result<()> {
+    let create_query: Option<String> = client
+        .query(
+            "SELECT create_table_query FROM system.tables WHERE database = currentDatabase() AND name = ?",
+        )
+        .bind(table)
+        .fetch_optional()
+        .await?;
+
+    let Some(create_query) = create_query else {
+        return Err(anyhow!("table does not exist"));
+    };
+
+    let current = table_ttl_days(&create_query);
+
+    let sql = match (days, current) {
+        (0, TableTtl::None) => return Ok(()),
+        (0, _) => format!("ALTER TABLE {table} REMOVE TTL"),
+        (days, TableTtl::Days(current)) if current == days => return Ok(()),
+        (days, _) => format!("ALTER TABLE {table} MODIFY TTL timestamp + INTERVAL {days} DAY"),
+    };
+
+    info!("Setting retention of '{table}' to {days} days");
+
+    client.query(&sql).execute().await?;
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TableTtl {
+    None,
+    Days(u32),
+    /// A TTL that is not a plain `timestamp + N days`
+    Other,
+}
+
+/// Finds the table-level TTL in a `CREATE TABLE` query as returned by `system.tables`.
+/// Column TTLs come before `ORDER BY` and are ignored.
+fn table_ttl_days(create_query: &str) -> TableTtl {
+    let Some((_, after_order)) = create_query.split_once("ORDER BY") else {
+        return TableTtl::None;
+    };
+
+    let Some((_, ttl)) = after_order.split_once(" TTL ") else {
+        return TableTtl::None;
+    };
+
+    let ttl = ttl.split(" SETTINGS ").next().unwrap_or(ttl).trim();
+
+    // clickhouse normalizes `INTERVAL N DAY` into `toIntervalDay(N)`
+    ttl.strip_prefix("timestamp + toIntervalDay(")
+        .and_then(|x| x.strip_suffix(')'))
+        .and_then(|x| x.parse().ok())
+        .map_or(TableTtl::Other, TableTtl::Days)
+}
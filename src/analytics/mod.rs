@@ -1,15 +1,20 @@
 use std::{
-    sync::{Arc, OnceLock},
+    path::Path,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
     time::{Duration, Instant},
 };
 
 use anyhow::{Result, anyhow, bail};
 use parking_lot::Mutex;
+use serde::Serialize;
 use server_shared::qunet::{
     message::channel,
     server::{ServerHandle, WeakServerHandle},
 };
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
 use crate::core::{
     handler::ConnectionHandler,
@@ -19,23 +24,85 @@ use crate::core::{
 mod config;
 mod migrations;
 mod models;
+mod spill;
 use config::Config;
-pub use models::LoginEvent;
+pub use models::{LoginEvent, ModerationEvent};
+use spill::SpillFile;
 
 #[cfg(debug_assertions)]
 const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
 #[cfg(not(debug_assertions))]
 const FLUSH_INTERVAL: Duration = Duration::from_secs(45);
 
+/// Upper bound for the delay between flush attempts while clickhouse is unreachable
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How many spilled events are read into memory and written to clickhouse at once
+const REPLAY_BATCH_SIZE: usize = 10_000;
+
+/// Minimum time between two "channel is full" warnings
+const DROP_WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+pub enum AnalyticsConnectionState {
+    Disabled,
+    Connected,
+    Disconnected,
+}
+
 pub enum Event {
     Login(LoginEvent),
+    Moderation(ModerationEvent),
+}
+
+/// Events waiting to be written to clickhouse
+#[derive(Default)]
+struct PendingEvents {
+    logins: Vec<LoginEvent>,
+    moderation: Vec<ModerationEvent>,
+}
+
+impl PendingEvents {
+    fn len(&self) -> usize {
+        self.logins.len() + self.moderation.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn clear(&mut self) {
+        self.logins.clear();
+        self.moderation.clear();
+    }
+}
+
+struct SpillFiles {
+    logins: SpillFile,
+    moderation: SpillFile,
+}
+
+impl SpillFiles {
+    async fn open(dir: &Path, max_events: usize) -> Self {
+        Self {
+            logins: SpillFile::open(dir, "login_events", max_events).await,
+            moderation: SpillFile::open(dir, "moderation_events", max_events).await,
+        }
+    }
+
+    fn has_events(&self) -> bool {
+        self.logins.has_events() || self.moderation.has_events()
+    }
 }
 
 pub struct AnalyticsModule {
     client: Option<clickhouse::Client>,
+    config: Arc<Config>,
     server: OnceLock<WeakServerHandle<ConnectionHandler>>,
     tx: channel::Sender<Event>,
     rx: Mutex<Option<channel::Receiver<Event>>>,
+    dropped_events: AtomicU64,
+    last_drop_warning: Mutex<Option<Instant>>,
+    connected_since: Mutex<Option<Instant>>,
 }
 
 impl AnalyticsModule {
@@ -43,32 +110,65 @@ impl AnalyticsModule {
         let client = self.client.as_ref().expect("client must be initialized");
         let rx = self.rx.lock().take().expect("receiver must be initialized");
 
-        // perform migrations
-        migrations::run(client).await.map_err(|e| anyhow!("Failed to run migrations: {e}"))?;
+        let spill_after = Duration::from_secs(self.config.spill_after_secs);
+        let mut spill = match &self.config.spill_directory {
+            Some(dir) => Some(SpillFiles::open(dir, self.config.max_spilled_events).await),
+            None => None,
+        };
 
-        let mut last_flush = Instant::now();
-        let mut pending_logins = Vec::new();
+        // connect (and run migrations) right away
+        let mut next_flush = Instant::now();
+        let mut retry_delay = FLUSH_INTERVAL;
+        let mut failing_since: Option<Instant> = None;
+        let mut pending = PendingEvents::default();
 
         loop {
-            let deadline = last_flush + FLUSH_INTERVAL;
-            if let Ok(ev) = tokio::time::timeout_at(deadline.into(), rx.recv()).await {
+            if let Ok(ev) = tokio::time::timeout_at(next_flush.into(), rx.recv()).await {
                 match ev {
                     Some(Event::Login(event)) => {
-                        pending_logins.push(event);
+                        pending.logins.push(event);
+                    }
+
+                    Some(Event::Moderation(event)) => {
+                        pending.moderation.push(event);
                     }
 
                     None => break,
                 }
             }
 
-            // flush either when the interval has passed or when we have too many pending events
-            let should_flush = last_flush.elapsed() > FLUSH_INTERVAL || pending_logins.len() > 250;
+            // flush either when the interval has passed or when we have too many pending events,
+            // the latter is ignored while disconnected so that we don't bypass the backoff
+            let should_flush =
+                Instant::now() >= next_flush || (self.is_connected() && pending.len() > 250);
+
+            if !should_flush {
+                continue;
+            }
+
+            match self.try_flush(client, &mut pending, spill.as_mut()).await {
+                Ok(()) => {
+                    failing_since = None;
+                    retry_delay = FLUSH_INTERVAL;
+                    next_flush = Instant::now() + FLUSH_INTERVAL;
+                }
 
-            if should_flush {
-                last_flush = Instant::now();
+                Err(e) => {
+                    if self.connected_since.lock().take().is_some() {
+                        warn!("Lost connection to clickhouse, will keep retrying");
+                    }
 
-                if let Err(e) = self.flush(client, &mut pending_logins).await {
                     error!("{e}");
+
+                    let since = *failing_since.get_or_insert_with(Instant::now);
+                    if since.elapsed() > spill_after {
+                        self.spill_pending(spill.as_mut(), &mut pending).await;
+                    } else {
+                        self.trim_pending(&mut pending);
+                    }
+
+                    next_flush = Instant::now() + retry_delay;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_INTERVAL);
                 }
             }
         }
@@ -76,12 +176,70 @@ impl AnalyticsModule {
         Ok(())
     }
 
-    async fn flush(&self, client: &clickhouse::Client, logins: &mut Vec<LoginEvent>) -> Result<()> {
-        if !logins.is_empty() {
-            self.flush_pending_logins(client, logins)
+    /// Connects if needed, then writes pending and spilled events
+    async fn try_flush(
+        &self,
+        client: &clickhouse::Client,
+        pending: &mut PendingEvents,
+        spill: Option<&mut SpillFiles>,
+    ) -> Result<()> {
+        if !self.is_connected() {
+            self.connect(client).await?;
+        } else if pending.is_empty() {
+            // nothing to write, make sure the connection is still alive
+            Self::health_check(client).await?;
+        }
+
+        self.flush(client, pending).await?;
+        self.replay_spilled(client, spill).await
+    }
+
+    async fn connect(&self, client: &clickhouse::Client) -> Result<()> {
+        Self::health_check(client).await?;
+
+        // migrations are idempotent, rerun them in case the database was recreated
+        migrations::run(client).await.map_err(|e| anyhow!("Failed to run migrations: {e}"))?;
+        migrations::apply_retention(client, &self.config.retention_days).await;
+
+        *self.connected_since.lock() = Some(Instant::now());
+        info!("Connected to clickhouse");
+
+        Ok(())
+    }
+
+    async fn health_check(client: &clickhouse::Client) -> Result<()> {
+        client
+            .query("SELECT 1")
+            .execute()
+            .await
+            .map_err(|e| anyhow!("clickhouse health check failed: {e}"))
+    }
+
+    /// Drops the oldest pending events if there are more than allowed by the config
+    fn trim_pending(&self, pending: &mut PendingEvents) {
+        let max = self.config.max_buffered_events;
+        let dropped =
+            trim_oldest(&mut pending.logins, max) + trim_oldest(&mut pending.moderation, max);
+
+        if dropped > 0 {
+            self.dropped_events.fetch_add(dropped as u64, Ordering::Relaxed);
+            warn!("Analytics buffer is full, dropped {dropped} events");
+        }
+    }
+
+    async fn flush(&self, client: &clickhouse::Client, pending: &mut PendingEvents) -> Result<()> {
+        if !pending.logins.is_empty() {
+            self.flush_pending_logins(client, &pending.logins)
                 .await
                 .map_err(|e| anyhow!("failed to flush login events: {e}"))?;
-            logins.clear();
+            pending.logins.clear();
+        }
+
+        if !pending.moderation.is_empty() {
+            self.flush_pending_moderation(client, &pending.moderation)
+                .await
+                .map_err(|e| anyhow!("failed to flush moderation events: {e}"))?;
+            pending.moderation.clear();
         }
 
         Ok(())
@@ -90,27 +248,157 @@ impl AnalyticsModule {
     async fn flush_pending_logins(
         &self,
         client: &clickhouse::Client,
-        logins: &mut Vec<LoginEvent>,
+        logins: &[LoginEvent],
     ) -> Result<()> {
         debug!("Writing {} login events", logins.len());
         let mut insert = client.insert::<LoginEvent>("login_events").await?;
-        for login in logins.drain(..) {
-            insert.write(&login).await?;
+        for login in logins {
+            insert.write(login).await?;
+        }
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn flush_pending_moderation(
+        &self,
+        client: &clickhouse::Client,
+        events: &[ModerationEvent],
+    ) -> Result<()> {
+        debug!("Writing {} moderation events", events.len());
+        let mut insert = client.insert::<ModerationEvent>("moderation_events").await?;
+        for event in events {
+            insert.write(event).await?;
         }
         insert.end().await?;
 
         Ok(())
     }
 
+    /// Moves pending events to the spill files, or drops them if spilling is disabled
+    async fn spill_pending(&self, spill: Option<&mut SpillFiles>, pending: &mut PendingEvents) {
+        if pending.is_empty() {
+            return;
+        }
+
+        let dropped = match spill {
+            Some(spill) => {
+                spill_into(&mut spill.logins, &pending.logins).await
+                    + spill_into(&mut spill.moderation, &pending.moderation).await
+            }
+
+            None => pending.len(),
+        };
+
+        if dropped > 0 {
+            self.dropped_events.fetch_add(dropped as u64, Ordering::Relaxed);
+            warn!("Clickhouse is unreachable, dropped {dropped} events");
+        }
+
+        pending.clear();
+    }
+
+    /// Writes events that were spilled to disk during an outage back to clickhouse
+    async fn replay_spilled(
+        &self,
+        client: &clickhouse::Client,
+        spill: Option<&mut SpillFiles>,
+    ) -> Result<()> {
+        let Some(spill) = spill.filter(|s| s.has_events()) else {
+            return Ok(());
+        };
+
+        // each table is cleared as soon as its own events are written,
+        // so a failure in one doesn't cause the other to be replayed twice
+        let logins = spill
+            .logins
+            .replay::<LoginEvent>(REPLAY_BATCH_SIZE, async |events| {
+                self.flush_pending_logins(client, events)
+                    .await
+                    .map_err(|e| anyhow!("failed to replay login events: {e}"))
+            })
+            .await?;
+
+        let moderation = spill
+            .moderation
+            .replay::<ModerationEvent>(REPLAY_BATCH_SIZE, async |events| {
+                self.flush_pending_moderation(client, events)
+                    .await
+                    .map_err(|e| anyhow!("failed to replay moderation events: {e}"))
+            })
+            .await?;
+
+        let count = logins + moderation;
+
+        info!("Replayed {count} spilled analytics events");
+
+        Ok(())
+    }
+
     pub fn log_event(&self, event: Event) {
-        if self.client.is_some() {
-            self.tx.send(event);
+        if self.client.is_some() && !self.tx.send(event) {
+            let dropped = self.dropped_events.fetch_add(1, Ordering::Relaxed) + 1;
+
+            let mut last_warning = self.last_drop_warning.lock();
+            if last_warning.is_none_or(|t| t.elapsed() > DROP_WARN_INTERVAL) {
+                *last_warning = Some(Instant::now());
+                warn!("Analytics channel is full, dropping events ({dropped} dropped in total)");
+            }
         }
     }
 
+    pub fn connection_state(&self) -> AnalyticsConnectionState {
+        if self.client.is_none() {
+            AnalyticsConnectionState::Disabled
+        } else if self.is_connected() {
+            AnalyticsConnectionState::Connected
+        } else {
+            AnalyticsConnectionState::Disconnected
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected_since.lock().is_some()
+    }
+
+    /// Total amount of events that were dropped, either due to a full channel or a clickhouse outage
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+
     pub fn log_login_event(&self, event: LoginEvent) {
         self.log_event(Event::Login(event));
     }
+
+    pub fn log_moderation_event(&self, event: ModerationEvent) {
+        self.log_event(Event::Moderation(event));
+    }
+}
+
+/// Drops the oldest events so that at most `max` remain, returns how many were dropped
+fn trim_oldest<T>(events: &mut Vec<T>, max: usize) -> usize {
+    let excess = events.len().saturating_sub(max);
+    events.drain(..excess);
+    excess
+}
+
+/// Writes events to the spill file, returns how many had to be dropped
+async fn spill_into<T: Serialize>(file: &mut SpillFile, events: &[T]) -> usize {
+    if events.is_empty() {
+        return 0;
+    }
+
+    match file.write(events).await {
+        Ok(dropped) => {
+            debug!("Spilled {} analytics events to disk", events.len() - dropped);
+            dropped
+        }
+
+        Err(e) => {
+            error!("failed to spill analytics events to disk: {e}");
+            events.len()
+        }
+    }
 }
 
 fn create_client(config: &Config) -> Result<Option<clickhouse::Client>> {
@@ -135,13 +423,17 @@ fn create_client(config: &Config) -> Result<Option<clickhouse::Client>> {
 
 impl ServerModule for AnalyticsModule {
     async fn new(config: Arc<Config>, _handler: &ConnectionHandler) -> ModuleInitResult<Self> {
-        let (tx, rx) = channel::new_channel(1024);
+        let (tx, rx) = channel::new_channel(config.channel_capacity);
 
         Ok(Self {
             client: create_client(&config)?,
+            config,
             server: OnceLock::new(),
             tx,
             rx: Mutex::new(Some(rx)),
+            dropped_events: AtomicU64::new(0),
+            last_drop_warning: Mutex::new(None),
+            connected_since: Mutex::new(None),
         })
     }
 
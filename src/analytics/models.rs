@@ -2,9 +2,9 @@ use std::net::{IpAddr, Ipv6Addr};
 
 use chrono::{DateTime, Utc};
 use clickhouse::Row;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Row)]
+#[derive(Serialize, Deserialize, Row)]
 pub struct LoginEvent {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     pub timestamp: DateTime<Utc>,
@@ -52,3 +52,23 @@ impl LoginEvent {
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Row)]
+pub struct ModerationEvent {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub timestamp: DateTime<Utc>,
+    pub action: heapless::String<16>,
+    pub issuer_id: i32,
+    pub target_id: i32,
+}
+
+impl ModerationEvent {
+    pub fn new(action: &str, issuer_id: i32, target_id: i32) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            action: convert_str(action),
+            issuer_id,
+            target_id,
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::warn;
+
+/// Stores events that could not be written to clickhouse in a local file (one JSON object per line),
+/// so they can be replayed once the connection is back.
+pub struct SpillFile {
+    path: PathBuf,
+    max_events: usize,
+    count: usize,
+}
+
+impl SpillFile {
+    /// Opens the spill file for the table, counting events that are left over from a previous run
+    pub async fn open(dir: &Path, table: &str, max_events: usize) -> Self {
+        let path = dir.join(format!("{table}.jsonl"));
+
+        let count = match count_lines(&path).await {
+            Ok(count) => count,
+            Err(e) => {
+                warn!("Failed to read spilled events from {path:?}: {e}");
+                0
+            }
+        };
+
+        Self { path, max_events, count }
+    }
+
+    /// Whether there are events left over from a previous run or outage
+    pub fn has_events(&self) -> bool {
+        self.count > 0
+    }
+
+    /// Appends the events to the file, dropping any over the limit. Returns how many were dropped.
+    pub async fn write<T: Serialize>(&mut self, events: &[T]) -> Result<usize> {
+        let space = self.max_events.saturating_sub(self.count);
+        let to_write = &events[..events.len().min(space)];
+
+        if !to_write.is_empty() {
+            let mut data = Vec::new();
+            for event in to_write {
+                serde_json::to_writer(&mut data, event)?;
+                data.push(b'\n');
+            }
+
+            if let Some(parent) = self.path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+
+            let mut file =
+                tokio::fs::OpenOptions::new().create(true).append(true).open(&self.path).await?;
+            file.write_all(&data).await?;
+
+            self.count += to_write.len();
+        }
+
+        Ok(events.len() - to_write.len())
+    }
+
+    /// Reads the file in batches of up to `batch_size` events and passes each batch to `insert`.
+    /// Events are removed from the file once the batch containing them was inserted,
+    /// if a batch fails, it and every event after it are kept for the next attempt.
+    /// Returns how many events were replayed.
+    pub async fn replay<T: DeserializeOwned>(
+        &mut self,
+        batch_size: usize,
+        mut insert: impl AsyncFnMut(&[T]) -> Result<()>,
+    ) -> Result<usize> {
+        let file = match tokio::fs::File::open(&self.path).await {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.count = 0;
+                return Ok(0);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        let mut batch = Vec::with_capacity(batch_size.min(self.count));
+        let mut batch_lines = 0;
+        let mut done_lines = 0;
+        let mut replayed = 0;
+
+        loop {
+            let line = lines.next_line().await?;
+            let at_end = line.is_none();
+
+            if let Some(line) = line {
+                batch_lines += 1;
+
+                if !line.is_empty() {
+                    match serde_json::from_str(&line) {
+                        Ok(ev) => batch.push(ev),
+                        Err(e) => warn!("Skipping malformed spilled event in {:?}: {e}", self.path),
+                    }
+                }
+            }
+
+            if batch.len() >= batch_size || (at_end && !batch.is_empty()) {
+                if let Err(e) = insert(&batch).await {
+                    drop(lines);
+                    self.drop_lines(done_lines).await?;
+                    return Err(e);
+                }
+
+                replayed += batch.len();
+                done_lines += batch_lines;
+                batch.clear();
+                batch_lines = 0;
+            }
+
+            if at_end {
+                break;
+            }
+        }
+
+        drop(lines);
+        self.clear().await?;
+
+        Ok(replayed)
+    }
+
+    /// Removes the first `n` lines from the file, keeping the rest
+    async fn drop_lines(&mut self, n: usize) -> Result<()> {
+        if n == 0 {
+            return Ok(());
+        }
+
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        let mut lines = BufReader::new(tokio::fs::File::open(&self.path).await?).lines();
+        let mut out = tokio::io::BufWriter::new(tokio::fs::File::create(&tmp_path).await?);
+        let mut index = 0;
+        let mut kept = 0;
+
+        while let Some(line) = lines.next_line().await? {
+            if index >= n && !line.is_empty() {
+                out.write_all(line.as_bytes()).await?;
+                out.write_all(b"\n").await?;
+                kept += 1;
+            }
+
+            index += 1;
+        }
+
+        out.flush().await?;
+        drop(out);
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        self.count = kept;
+        Ok(())
+    }
+
+    pub async fn clear(&mut self) -> Result<()> {
+        self.count = 0;
+
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Counts the events in a spill file without loading it into memory
+async fn count_lines(path: &Path) -> std::io::Result<usize> {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut lines = BufReader::new(file).lines();
+    let mut count = 0;
+
+    while let Some(line) = lines.next_line().await? {
+        if !line.is_empty() {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
@@ -99,8 +99,12 @@ impl ArgonClient {
         &self.inner.url
     }
 
+    pub fn is_connected(&self) -> bool {
+        self.inner.connected.load(Ordering::Relaxed)
+    }
+
     pub fn connected_for(&self) -> Option<Duration> {
-        if self.inner.connected.load(Ordering::Relaxed) {
+        if self.is_connected() {
             Some(self.inner.connected_at.lock().elapsed())
         } else {
             None
@@ -55,11 +55,16 @@ impl AuthModule {
             return ArgonConnectionState::Disabled;
         };
 
-        let Some(_) = client.connected_for() else {
-            return ArgonConnectionState::Disconnected;
-        };
+        if client.is_connected() {
+            ArgonConnectionState::Connected
+        } else {
+            ArgonConnectionState::Disconnected
+        }
+    }
 
-        ArgonConnectionState::Connected
+    /// Whether the argon client is currently connected, `false` if verification is disabled
+    pub fn argon_connected(&self) -> bool {
+        self.argon_client.as_ref().is_some_and(|client| client.is_connected())
     }
 
     pub fn argon_connected_for(&self) -> Option<Duration> {
@@ -4,7 +4,7 @@ use std::{
         Arc, OnceLock,
         atomic::{AtomicBool, AtomicI32, AtomicU16, AtomicU32, AtomicU64, Ordering},
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use nohash_hasher::IntSet;
@@ -16,6 +16,7 @@ use server_shared::{
     events::{EventEncoder, EventRateLimiter, EventRateLimiterOptions},
     qunet::transport::RateLimiter,
 };
+use smallvec::SmallVec;
 
 use crate::{
     auth::ClientAccountData,
@@ -23,6 +24,28 @@ use crate::{
     users::{ComputedRole, UserPunishment},
 };
 
+/// Messages that are rate limited separately from the global message limit
+#[derive(Clone, Copy, Debug)]
+pub enum LimitedMessage {
+    CreateRoom,
+    NoticeEveryone,
+    GlobalPlayerList,
+    RoomList,
+    PlayerCounts,
+    LevelList,
+    RoomPlayers,
+}
+
+impl LimitedMessage {
+    pub const COUNT: usize = 7;
+}
+
+struct PasscodeAttempts {
+    room_id: u32,
+    failures: u32,
+    last_failure: Instant,
+}
+
 pub struct ClientData {
     account_data: OnceLock<ClientAccountData>,
     account_id: AtomicI32, // redundant, for faster access
@@ -47,12 +70,25 @@ pub struct ClientData {
     settings: Mutex<UserSettings>,
 
     invite_limiter: Mutex<RateLimiter>,
+    /// Limiter for all messages, along with the per-second limit it was created with
+    message_limiter: Mutex<Option<(u32, RateLimiter)>>,
+    /// Limiters for each message kind, along with the limit and burst they were created with
+    message_limiters: Mutex<[Option<(u32, u32, RateLimiter)>; LimitedMessage::COUNT]>,
+    passcode_attempts: Mutex<SmallVec<[PasscodeAttempts; 2]>>,
+    last_notice_reply: Mutex<Option<Instant>>,
+    last_room_created: Mutex<Option<Instant>>,
+    connected_at: Instant,
 
     event_encoder: OnceLock<EventEncoder>,
     event_limiter: Mutex<EventRateLimiter>,
 }
 
 impl ClientData {
+    /// How long ago the connection was established
+    pub fn connected_for(&self) -> Duration {
+        self.connected_at.elapsed()
+    }
+
     pub fn account_data(&self) -> Option<&ClientAccountData> {
         if self.deauthorized.load(Ordering::Relaxed) {
             return None;
@@ -276,6 +312,117 @@ impl ClientData {
     pub fn try_invite(&self) -> bool {
         self.invite_limiter.lock().consume()
     }
+
+    /// Returns whether the client entered a wrong passcode for this room at least `max_attempts` times,
+    /// with less than `lockout` passing since the last failure
+    pub fn is_passcode_locked_out(
+        &self,
+        room_id: u32,
+        max_attempts: u32,
+        lockout: Duration,
+    ) -> bool {
+        let mut attempts = self.passcode_attempts.lock();
+        attempts.retain(|a| a.last_failure.elapsed() < lockout);
+
+        attempts.iter().any(|a| a.room_id == room_id && a.failures >= max_attempts)
+    }
+
+    pub fn record_passcode_failure(&self, room_id: u32) {
+        let mut attempts = self.passcode_attempts.lock();
+
+        if let Some(a) = attempts.iter_mut().find(|a| a.room_id == room_id) {
+            a.failures += 1;
+            a.last_failure = Instant::now();
+            return;
+        }
+
+        // don't let this grow indefinitely if someone goes through many rooms
+        if attempts.len() >= 16 {
+            attempts.remove(0);
+        }
+
+        attempts.push(PasscodeAttempts {
+            room_id,
+            failures: 1,
+            last_failure: Instant::now(),
+        });
+    }
+
+    pub fn clear_passcode_failures(&self, room_id: u32) {
+        self.passcode_attempts.lock().retain(|a| a.room_id != room_id);
+    }
+
+    /// Returns `false` if less than `cooldown` has passed since the last notice reply
+    pub fn try_notice_reply(&self, cooldown: Duration) -> bool {
+        let mut last = self.last_notice_reply.lock();
+
+        if last.is_some_and(|t| t.elapsed() < cooldown) {
+            return false;
+        }
+
+        *last = Some(Instant::now());
+        true
+    }
+
+    /// Returns whether the client can create a room, given the cooldown between room creations
+    pub fn can_create_room(&self, cooldown: Duration) -> bool {
+        self.last_room_created.lock().is_none_or(|t| t.elapsed() >= cooldown)
+    }
+
+    /// Starts the room creation cooldown, should be called once a room was successfully created
+    pub fn mark_room_created(&self) {
+        *self.last_room_created.lock() = Some(Instant::now());
+    }
+
+    /// Returns whether the client is allowed to send this message, given the limit per minute.
+    /// The limiter is created on first use and recreated if the limit changes, e.g. after a reload.
+    /// `burst` is how many messages can be sent at once before the limit kicks in,
+    /// 0 allows the entire per-minute amount at once
+    pub fn try_consume_message(&self, kind: LimitedMessage, per_minute: u32, burst: u32) -> bool {
+        if per_minute == 0 {
+            return true;
+        }
+
+        let burst = if burst == 0 { per_minute } else { burst.min(per_minute) };
+
+        let mut limiters = self.message_limiters.lock();
+        let slot = &mut limiters[kind as usize];
+
+        if slot.as_ref().is_none_or(|(pm, b, _)| (*pm, *b) != (per_minute, burst)) {
+            *slot = Some((
+                per_minute,
+                burst,
+                RateLimiter::new_precise(
+                    Duration::from_secs(60).as_nanos() as u64 / per_minute as u64,
+                    burst as _,
+                ),
+            ));
+        }
+
+        slot.as_mut().unwrap().2.consume()
+    }
+
+    /// Returns whether the client is allowed to send another message of any kind, given the limit per second.
+    /// Like `try_consume_message`, the limiter is recreated if the limit changes.
+    pub fn try_consume_any_message(&self, per_second: u32) -> bool {
+        if per_second == 0 {
+            return true;
+        }
+
+        let mut limiter = self.message_limiter.lock();
+
+        if limiter.as_ref().is_none_or(|(ps, _)| *ps != per_second) {
+            *limiter = Some((
+                per_second,
+                RateLimiter::new_precise(
+                    Duration::from_secs(1).as_nanos() as u64 / per_second as u64,
+                    per_second as _,
+                ),
+            ));
+        }
+
+        limiter.as_mut().unwrap().1.consume()
+    }
 }
 
 impl Default for ClientData {
@@ -313,6 +460,12 @@ impl Default for ClientData {
                 Duration::from_secs(4).as_nanos() as u64,
                 25,
             )),
+            message_limiter: Mutex::new(None),
+            message_limiters: Mutex::new([const { None }; LimitedMessage::COUNT]),
+            passcode_attempts: Mutex::new(SmallVec::new()),
+            last_notice_reply: Mutex::new(None),
+            last_room_created: Mutex::new(None),
+            connected_at: Instant::now(),
 
             event_encoder: OnceLock::new(),
             event_limiter: Mutex::new(EventRateLimiter::new(EventRateLimiterOptions {
@@ -322,3 +475,24 @@ impl Default for ClientData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY_IDS: &[u64] = &[0, 1, u32::MAX as u64, 1 << 32, 1 << 63, u64::MAX];
+
+    #[test]
+    fn client_session_id_round_trips() {
+        let data = ClientData::default();
+        assert!(data.session_id().is_zero());
+
+        let mut prev = 0;
+        for &raw in BOUNDARY_IDS {
+            assert_eq!(data.set_session_id(raw), prev);
+            assert_eq!(data.session_id_u64(), raw);
+            assert_eq!(data.session_id().as_u64(), raw);
+            prev = raw;
+        }
+    }
+}
@@ -42,7 +42,7 @@ fn default_quic_tls_key() -> String {
     String::new()
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Validate)]
 #[serde(deny_unknown_fields)]
 pub struct QuicConfig {
     /// Whether to enable incoming QUIC connections. This requires all the other parameters in this section to be set.
@@ -79,7 +79,7 @@ fn default_tcp_address() -> String {
     "[::]:4340".into()
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Validate)]
 #[serde(deny_unknown_fields)]
 pub struct TcpConfig {
     /// Whether to enable incoming TCP connections. This requires the "address" option to be set.
@@ -108,7 +108,7 @@ fn default_ws_address() -> String {
     "[::]:4341".into()
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Validate)]
 #[serde(deny_unknown_fields)]
 pub struct WsConfig {
     /// Whether to enable incoming WebSocket connections. This requires the "address" option to be set.
@@ -141,7 +141,7 @@ fn default_udp_address() -> String {
     "[::]:4340".into()
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Validate)]
 #[serde(deny_unknown_fields)]
 pub struct UdpConfig {
     /// Whether to enable incoming UDP connections. This requires the "address" option to be set.
@@ -190,6 +190,190 @@ fn default_gs_quic_address() -> Option<String> {
     None
 }
 
+fn default_gs_reconnect_grace_secs() -> u64 {
+    15
+}
+
+fn default_gs_liveness_check_interval_secs() -> u64 {
+    0
+}
+
+fn default_gs_idle_timeout_secs() -> u64 {
+    0
+}
+
+fn default_level_count_reconcile_secs() -> u64 {
+    300
+}
+
+fn default_login_timeout_secs() -> u64 {
+    30
+}
+
+// Rate limits
+
+fn default_create_room_limit() -> u32 {
+    10
+}
+
+fn default_notice_everyone_limit() -> u32 {
+    2
+}
+
+fn default_global_player_list_limit() -> u32 {
+    20
+}
+
+fn default_room_list_limit() -> u32 {
+    60
+}
+
+fn default_player_counts_limit() -> u32 {
+    60
+}
+
+fn default_level_list_limit() -> u32 {
+    30
+}
+
+fn default_room_players_limit() -> u32 {
+    60
+}
+
+fn default_staff_limit_multiplier() -> u32 {
+    5
+}
+
+fn default_messages_per_second() -> u32 {
+    16
+}
+
+fn default_staff_messages_per_second() -> u32 {
+    64
+}
+
+/// Message rate limits, the limits for specific messages apply on top of the per-second limit.
+/// Unless noted otherwise, values are in messages per minute, 0 means unlimited.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct MessageRateLimits {
+    #[serde(default = "default_create_room_limit")]
+    pub create_room: u32,
+    #[serde(default = "default_notice_everyone_limit")]
+    pub notice_everyone: u32,
+    #[serde(default = "default_global_player_list_limit")]
+    pub global_player_list: u32,
+    #[serde(default = "default_room_list_limit")]
+    pub room_list: u32,
+    #[serde(default = "default_player_counts_limit")]
+    pub player_counts: u32,
+    #[serde(default = "default_level_list_limit")]
+    pub level_list: u32,
+    #[serde(default = "default_room_players_limit")]
+    pub room_players: u32,
+    /// How many of each message can be sent in quick succession before the limit starts spacing
+    /// them out, e.g. a room list limit of 30 with a burst of 1 allows one room list every 2 seconds.
+    /// 0 allows the entire per-minute amount at once.
+    #[serde(default)]
+    pub max_burst: u32,
+    /// The limits above are multiplied by this for clients whose role can moderate,
+    /// so staff tools don't get throttled during bulk actions. 0 exempts them entirely.
+    #[serde(default = "default_staff_limit_multiplier")]
+    pub staff_multiplier: u32,
+    /// How many messages of any kind a client can send per second, 0 means unlimited.
+    /// Clients whose role can moderate are only held to `staff_per_second`.
+    #[serde(default = "default_messages_per_second")]
+    pub per_second: u32,
+    /// Per-second message limit for clients whose role can moderate. The transport enforces the higher
+    /// of this and `per_second` for every connection, so changing it requires a restart.
+    #[serde(default = "default_staff_messages_per_second")]
+    pub staff_per_second: u32,
+    /// Whether to log a warning every time a message is dropped for going over the limit
+    #[serde(default)]
+    pub warn_on_drop: bool,
+}
+
+impl Default for MessageRateLimits {
+    fn default() -> Self {
+        Self {
+            create_room: default_create_room_limit(),
+            notice_everyone: default_notice_everyone_limit(),
+            global_player_list: default_global_player_list_limit(),
+            room_list: default_room_list_limit(),
+            player_counts: default_player_counts_limit(),
+            level_list: default_level_list_limit(),
+            room_players: default_room_players_limit(),
+            max_burst: 0,
+            staff_multiplier: default_staff_limit_multiplier(),
+            per_second: default_messages_per_second(),
+            staff_per_second: default_staff_messages_per_second(),
+            warn_on_drop: false,
+        }
+    }
+}
+
+impl MessageRateLimits {
+    /// The per-second limit given to the transport, regular clients are limited further by the handler
+    pub fn transport_limit(&self) -> u32 {
+        self.staff_per_second.max(self.per_second).max(1)
+    }
+}
+
+// Misc
+
+fn default_motd() -> String {
+    String::new()
+}
+
+fn default_scheduled_notice_interval() -> u64 {
+    3600
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeAudience {
+    #[default]
+    Everyone,
+    /// Only players who are currently in a level
+    InLevel,
+    /// Only players who are not in a level
+    InMenus,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduledNotice {
+    pub message: String,
+    /// How often (in seconds) the notice is sent, at least 60
+    #[serde(default = "default_scheduled_notice_interval")]
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub audience: NoticeAudience,
+    /// Whether the notice is sent, can be changed without a restart
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_connection_log_sample_rate() -> u32 {
+    100
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionLogging {
+    /// Don't log incoming connections
+    Off,
+    /// Log one in every `connection_log_sample_rate` connections
+    Sampled,
+    /// Log every incoming connection
+    #[default]
+    All,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, Validate)]
 #[serde(deny_unknown_fields)]
 pub struct CoreConfig {
@@ -232,6 +416,23 @@ pub struct CoreConfig {
     /// Address for accepting QUIC connections from game servers. If blank, QUIC is not used.
     #[serde(default = "default_gs_quic_address")]
     pub gs_quic_address: Option<String>,
+    /// Maximum amount of rooms each game server can host at once, 0 means unlimited.
+    /// Creating a room on a server at capacity fails.
+    #[serde(default)]
+    pub gs_max_rooms: u32,
+    /// How long (in seconds) a disconnected game server is kept around, so that it can reconnect
+    /// without its rooms being closed. 0 removes game servers immediately.
+    #[serde(default = "default_gs_reconnect_grace_secs")]
+    pub gs_reconnect_grace_secs: u64,
+    /// How often (in seconds) game servers are checked for being responsive. The central server
+    /// does not ping them, any message they send counts as a sign of life. 0 disables the check.
+    #[serde(default = "default_gs_liveness_check_interval_secs")]
+    pub gs_liveness_check_interval_secs: u64,
+    /// How long (in seconds) a game server can go without sending anything before it is
+    /// considered hung and gets disconnected. Idle game servers must send a keepalive (any message
+    /// works) more often than this, or they will be disconnected too. 0 disables the check.
+    #[serde(default = "default_gs_idle_timeout_secs")]
+    pub gs_idle_timeout_secs: u64,
 
     /// Override for the base URL used for communication with the GD servers.
     /// Change this if you are hosting a server for a GDPS.
@@ -241,6 +442,38 @@ pub struct CoreConfig {
     /// Auth token for GD api requests, optional.
     #[serde(default)]
     pub gd_api_auth_token: Option<String>,
+
+    /// Per-message rate limits. Changes also apply to clients that are already connected.
+    #[serde(default)]
+    pub message_rate_limits: MessageRateLimits,
+
+    /// Message of the day, shown to players as a notice after they log in. Leave empty to disable.
+    /// This option can be changed without restarting the server.
+    #[serde(default = "default_motd")]
+    pub motd: String,
+
+    /// Notices that are automatically sent to players at a fixed interval.
+    /// The notices can be edited without a restart, adding or removing them or changing the interval can not.
+    #[serde(default)]
+    pub scheduled_notices: Vec<ScheduledNotice>,
+
+    /// Which incoming connections to log, rejected connections are always logged
+    #[serde(default)]
+    pub log_connections: ConnectionLogging,
+    /// In sampled mode, how many connections there are for every one that gets logged
+    #[serde(default = "default_connection_log_sample_rate")]
+    #[validate(range(min = 1))]
+    pub connection_log_sample_rate: u32,
+
+    /// How often (in seconds) level player counts and the session index are checked against the
+    /// connected clients, fixing any entries that drifted. 0 disables the check.
+    #[serde(default = "default_level_count_reconcile_secs")]
+    pub level_count_reconcile_secs: u64,
+
+    /// How long (in seconds) a client can stay connected without logging in before it gets
+    /// disconnected. 0 disables the timeout.
+    #[serde(default = "default_login_timeout_secs")]
+    pub login_timeout_secs: u64,
 }
 
 impl Default for CoreConfig {
@@ -258,13 +491,81 @@ impl Default for CoreConfig {
             gs_password: default_gs_password(),
             gs_tcp_address: default_gs_tcp_address(),
             gs_quic_address: default_gs_quic_address(),
+            gs_max_rooms: 0,
+            gs_reconnect_grace_secs: default_gs_reconnect_grace_secs(),
+            gs_liveness_check_interval_secs: default_gs_liveness_check_interval_secs(),
+            gs_idle_timeout_secs: default_gs_idle_timeout_secs(),
             gd_api_base_url: None,
             gd_api_auth_token: None,
+            message_rate_limits: MessageRateLimits::default(),
+            motd: default_motd(),
+            scheduled_notices: Vec::new(),
+            log_connections: ConnectionLogging::default(),
+            connection_log_sample_rate: default_connection_log_sample_rate(),
+            level_count_reconcile_secs: default_level_count_reconcile_secs(),
+            login_timeout_secs: default_login_timeout_secs(),
         }
     }
 }
 
 impl CoreConfig {
+    /// Returns the names of options that differ between the two configs and need a restart to apply
+    pub fn restart_required_changes(&self, new: &CoreConfig) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        let mut check = |name, differs: bool| {
+            if differs {
+                changed.push(name);
+            }
+        };
+
+        check("memory_usage", self.memory_usage != new.memory_usage);
+        check("compression_level", self.compression_level != new.compression_level);
+        // the logger config is defined in server-shared, compare its serialized form
+        check(
+            "logging",
+            serde_json::to_value(&self.logging).ok() != serde_json::to_value(&new.logging).ok(),
+        );
+        check("quic", self.quic != new.quic);
+        check("tcp", self.tcp != new.tcp);
+        check("ws", self.ws != new.ws);
+        check("udp", self.udp != new.udp);
+        check("qdb_path", self.qdb_path != new.qdb_path);
+        check("enable_stat_tracking", self.enable_stat_tracking != new.enable_stat_tracking);
+        check("gs_password", self.gs_password != new.gs_password);
+        check("gs_tcp_address", self.gs_tcp_address != new.gs_tcp_address);
+        check("gs_quic_address", self.gs_quic_address != new.gs_quic_address);
+        check(
+            "gs_liveness_check_interval_secs",
+            self.gs_liveness_check_interval_secs != new.gs_liveness_check_interval_secs,
+        );
+        // the timeout itself is re-read, but the check is only scheduled if it was enabled at startup
+        check(
+            "gs_idle_timeout_secs",
+            (self.gs_idle_timeout_secs == 0) != (new.gs_idle_timeout_secs == 0),
+        );
+        check(
+            "message_rate_limits.staff_per_second",
+            self.message_rate_limits.transport_limit() != new.message_rate_limits.transport_limit(),
+        );
+        check("gd_api_base_url", self.gd_api_base_url != new.gd_api_base_url);
+        check("gd_api_auth_token", self.gd_api_auth_token != new.gd_api_auth_token);
+        check(
+            "scheduled_notices",
+            self.scheduled_notices.len() != new.scheduled_notices.len()
+                || self
+                    .scheduled_notices
+                    .iter()
+                    .zip(new.scheduled_notices.iter())
+                    .any(|(a, b)| a.interval_secs != b.interval_secs),
+        );
+        check(
+            "level_count_reconcile_secs",
+            self.level_count_reconcile_secs != new.level_count_reconcile_secs,
+        );
+
+        changed
+    }
+
     pub fn replace_with_env(&mut self) {
         env_replace("GLOBED_CORE_MEMORY_USAGE", &mut self.memory_usage);
         env_replace("GLOBED_CORE_COMPRESSION_LEVEL", &mut self.compression_level);
@@ -297,8 +598,18 @@ impl CoreConfig {
         env_replace("GLOBED_CORE_GS_PASSWORD", &mut self.gs_password);
         env_replace("GLOBED_CORE_GS_TCP_ADDRESS", &mut self.gs_tcp_address);
         env_replace("GLOBED_CORE_GS_QUIC_ADDRESS", &mut self.gs_quic_address);
+        env_replace("GLOBED_CORE_GS_MAX_ROOMS", &mut self.gs_max_rooms);
+        env_replace("GLOBED_CORE_GS_RECONNECT_GRACE_SECS", &mut self.gs_reconnect_grace_secs);
+        env_replace(
+            "GLOBED_CORE_GS_LIVENESS_CHECK_INTERVAL_SECS",
+            &mut self.gs_liveness_check_interval_secs,
+        );
+        env_replace("GLOBED_CORE_GS_IDLE_TIMEOUT_SECS", &mut self.gs_idle_timeout_secs);
+        env_replace("GLOBED_CORE_LOGIN_TIMEOUT_SECS", &mut self.login_timeout_secs);
 
         env_replace("GLOBED_CORE_GD_API_BASE_URL", &mut self.gd_api_base_url);
         env_replace("GLOBED_CORE_GD_API_AUTH_TOKEN", &mut self.gd_api_auth_token);
+
+        env_replace("GLOBED_CORE_MOTD", &mut self.motd);
     }
 }
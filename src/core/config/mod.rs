@@ -8,6 +8,7 @@ use arc_swap::ArcSwap;
 use serde::{Serialize, de::DeserializeOwned};
 use server_shared::{TypeMap, config::env_replace};
 use thiserror::Error;
+use tracing::warn;
 use validator::{Validate, ValidationErrors};
 
 trait ConfigTrait: Send + Sync + Default + DeserializeOwned + Serialize + 'static {}
@@ -88,6 +89,10 @@ impl Config {
         cfg.replace_with_env();
         cfg.validate()?;
 
+        for name in self.core_config.load().restart_required_changes(&cfg) {
+            warn!("Option 'core.{name}' was changed, the server must be restarted to apply it");
+        }
+
         self.core_config.store(Arc::new(cfg));
         Ok(())
     }
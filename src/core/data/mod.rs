@@ -19,15 +19,22 @@ macro_rules! encode_message_dyn {
     }
 }
 
+// The two macros below record failures in the handler, keyed by the call site,
+// so that capacity estimates which turn out too small can be found and tuned.
+
 macro_rules! encode_message_heap {
-    ($this:expr, $estcap:expr, $msg:ident => $code:expr) => {
-        server_shared::encode_message_heap!(server_shared::schema::main, $this.server(), $estcap, $msg => $code)
-    }
+    ($this:expr, $estcap:expr, $msg:ident => $code:expr) => {{
+        let estcap = $estcap;
+        server_shared::encode_message_heap!(server_shared::schema::main, $this.server(), estcap, $msg => $code)
+            .inspect_err(|e| $this.record_encode_failure(concat!(file!(), ":", line!()), estcap, e))
+    }}
 }
 
 macro_rules! encode_message {
+    // the capacity is not bound to a variable here, as it is used for a stack allocated buffer
     ($this:expr, $estcap:expr, $msg:ident => $code:expr) => {
         server_shared::encode_message!(server_shared::schema::main, $this.server(), $estcap, $msg => $code)
+            .inspect_err(|e| $this.record_encode_failure(concat!(file!(), ":", line!()), $estcap, e))
     }
 }
 
@@ -1,13 +1,15 @@
 use std::{
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     sync::{
         Arc, OnceLock,
         atomic::{AtomicBool, Ordering},
     },
+    time::{Duration, Instant},
 };
 
 use anyhow::anyhow;
 use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
 use server_shared::{
     data::{GameServerData, SRVC_PROTOCOL_VERSION},
     encoding::EncodeMessageError,
@@ -30,14 +32,24 @@ use tracing::{debug, error, info, warn};
 use super::data;
 use crate::{
     auth::AuthModule,
-    core::{data::heapless_str_from_reader, handler::ConnectionHandler},
+    core::{data::heapless_str_from_reader, handler::ConnectionHandler, util::constant_time_eq},
     users::UsersModule,
 };
 
+/// After this many failed logins from the same IP, further connections are refused for a while
+const MAX_FAILED_LOGINS: u32 = 5;
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(300);
+
+struct FailedLogins {
+    count: u32,
+    last_attempt: Instant,
+}
+
 pub struct GameServerHandler {
     password: String,
     server: OnceLock<WeakServerHandle<Self>>,
     main_server: WeakServerHandle<ConnectionHandler>,
+    failed_logins: Mutex<FxHashMap<IpAddr, FailedLogins>>,
 }
 
 pub type ClientStateHandle = Arc<ClientState<GameServerHandler>>;
@@ -56,6 +68,7 @@ pub struct GameServerClientData {
     authorized: AtomicBool,
     srvc_handshaked: AtomicBool,
     status_data: Mutex<SrvStatusData>,
+    last_seen: Mutex<Instant>,
 }
 
 impl GameServerClientData {
@@ -64,6 +77,7 @@ impl GameServerClientData {
             authorized: AtomicBool::new(false),
             srvc_handshaked: AtomicBool::new(false),
             status_data: Mutex::new(SrvStatusData::default()),
+            last_seen: Mutex::new(Instant::now()),
         }
     }
 
@@ -90,6 +104,15 @@ impl GameServerClientData {
     pub fn status_data(&self) -> SrvStatusData {
         self.status_data.lock().clone()
     }
+
+    /// Time of the last message received from this server
+    pub fn last_seen(&self) -> Instant {
+        *self.last_seen.lock()
+    }
+
+    fn touch(&self) {
+        *self.last_seen.lock() = Instant::now();
+    }
 }
 
 impl GameServerHandler {
@@ -98,7 +121,33 @@ impl GameServerHandler {
             password,
             server: OnceLock::new(),
             main_server,
+            failed_logins: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    /// Returns whether this IP has failed to log in too many times recently
+    fn is_login_blocked(&self, ip: IpAddr) -> bool {
+        let mut failed = self.failed_logins.lock();
+        failed.retain(|_, f| f.last_attempt.elapsed() < FAILED_LOGIN_WINDOW);
+
+        failed.get(&ip).is_some_and(|f| f.count >= MAX_FAILED_LOGINS)
+    }
+
+    /// Records a failed login from this IP, returns the amount of recent failures
+    fn record_failed_login(&self, ip: IpAddr) -> u32 {
+        let mut failed = self.failed_logins.lock();
+        let entry = failed.entry(ip).or_insert(FailedLogins {
+            count: 0,
+            last_attempt: Instant::now(),
+        });
+
+        if entry.last_attempt.elapsed() >= FAILED_LOGIN_WINDOW {
+            entry.count = 0;
         }
+
+        entry.count += 1;
+        entry.last_attempt = Instant::now();
+        entry.count
     }
 
     fn server(&self) -> QunetServerHandle<Self> {
@@ -139,7 +188,26 @@ impl GameServerHandler {
             return self.send_login_failed(client, "already logged in").await;
         }
 
+        // also checked on connect, but a client could retry multiple times on the same connection
+        if self.is_login_blocked(client.address.ip()) {
+            return self.send_login_failed(client, "too many failed login attempts").await;
+        }
+
         if !constant_time_eq(password, &self.password) {
+            let failures = self.record_failed_login(client.address.ip());
+
+            warn!(
+                "[{}] Game server '{}' tried to connect with an invalid password ({failures} recent failures), make sure gs_password matches on both servers",
+                client.address, data.string_id
+            );
+
+            if failures >= MAX_FAILED_LOGINS {
+                warn!(
+                    "[{}] Too many failed game server logins, refusing connections from this address for {:?}",
+                    client.address, FAILED_LOGIN_WINDOW
+                );
+            }
+
             return self.send_login_failed(client, "invalid password").await;
         }
 
@@ -215,11 +283,7 @@ impl GameServerHandler {
         Ok(())
     }
 
-    fn handle_srvc_handshake(
-        &self,
-        _client: &ClientStateHandle,
-        data: &[u8],
-    ) -> anyhow::Result<()> {
+    fn handle_srvc_handshake(&self, client: &ClientStateHandle, data: &[u8]) -> anyhow::Result<()> {
         // expect srvc handshake as the first message
         let mut reader = ByteReader::new(data);
 
@@ -229,6 +293,12 @@ impl GameServerHandler {
 
         let version = reader.read_u32()?;
         if version != SRVC_PROTOCOL_VERSION {
+            // other handshake failures are usually not game servers at all, but this is a broken deploy
+            warn!(
+                "[{}] rejecting game server with protocol {version}, this central server uses protocol {SRVC_PROTOCOL_VERSION}",
+                client.address
+            );
+
             return Err(anyhow!(
                 "incompatible server versions, this central server uses protocol {}, while the game server uses {}",
                 SRVC_PROTOCOL_VERSION,
@@ -263,6 +333,13 @@ impl AppHandler for GameServerHandler {
             return Err("server not initialized yet".into());
         }
 
+        if self.is_login_blocked(address.ip()) {
+            debug!(
+                "[{connection_id} @ {address} ({kind})] Refusing game server connection, too many failed logins"
+            );
+            return Err("too many failed login attempts".into());
+        }
+
         info!("[{connection_id} @ {address} ({kind})] Game server connection attempt");
 
         Ok(GameServerClientData::new())
@@ -295,6 +372,9 @@ impl AppHandler for GameServerHandler {
             return;
         }
 
+        // any message counts as a sign of life
+        client.touch();
+
         let result = data::decode_message_match!(self, data, _unpacked_data, {
             LoginSrv(message) => {
                 let password = message.get_password()?.to_str()?;
@@ -337,17 +417,3 @@ impl AppHandler for GameServerHandler {
         }
     }
 }
-
-fn constant_time_eq(a: &str, b: &str) -> bool {
-    if a.len() != b.len() {
-        return false;
-    }
-
-    let mut result = 0u8;
-
-    for (a_byte, b_byte) in a.bytes().zip(b.bytes()) {
-        result |= a_byte ^ b_byte;
-    }
-
-    result == 0
-}
@@ -1,5 +1,8 @@
 use std::{
-    sync::{Arc, OnceLock},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU32, Ordering},
+    },
     time::{Duration, Instant},
 };
 
@@ -17,6 +20,7 @@ use server_shared::{
     encoding::EncodeMessageError,
 };
 use thiserror::Error;
+use tracing::warn;
 
 use super::data;
 use crate::core::game_server::GameServerHandler;
@@ -25,6 +29,9 @@ use crate::core::game_server::GameServerHandler;
 pub struct StoredGameServer {
     qclient: Arc<ClientState<GameServerHandler>>,
     connected_at: Instant,
+    room_count: Arc<AtomicU32>,
+    /// Set when the server disconnects and is waiting to either reconnect or be removed
+    disconnected_at: Option<Instant>,
     pub data: GameServerData,
 }
 
@@ -36,6 +43,28 @@ impl StoredGameServer {
     pub fn status_data(&self) -> SrvStatusData {
         self.qclient.status_data()
     }
+
+    pub fn room_count(&self) -> u32 {
+        self.room_count.load(Ordering::Relaxed)
+    }
+
+    /// Whether the server hosts at least `max_rooms` rooms, 0 means unlimited
+    pub fn is_full(&self, max_rooms: u32) -> bool {
+        max_rooms != 0 && self.room_count() >= max_rooms
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected_at.is_some()
+    }
+
+    pub fn disconnected_at(&self) -> Option<Instant> {
+        self.disconnected_at
+    }
+
+    /// Time of the last message received from the server
+    pub fn last_seen(&self) -> Instant {
+        self.qclient.last_seen()
+    }
 }
 
 #[derive(Default)]
@@ -85,7 +114,9 @@ impl GameServerManager {
         &self,
         server: Arc<ClientState<GameServerHandler>>,
         mut data: GameServerData,
-    ) {
+    ) -> u8 {
+        let mut id = 0;
+
         self.servers.rcu(|servers| {
             let mut servers = (**servers).clone();
 
@@ -101,10 +132,15 @@ impl GameServerManager {
             servers.push(StoredGameServer {
                 qclient: server.clone(),
                 connected_at: Instant::now(),
+                room_count: Arc::new(AtomicU32::new(0)),
+                disconnected_at: None,
                 data: data.clone(),
             });
+            id = data.id;
             servers
         });
+
+        id
     }
 
     pub fn remove_server(
@@ -127,12 +163,120 @@ impl GameServerManager {
         ret
     }
 
+    /// Marks the server as disconnected without removing it, so that it can reconnect within
+    /// the grace period. Returns the updated server, `None` if it was not found.
+    pub fn detach_server(
+        &self,
+        server: &ClientState<GameServerHandler>,
+    ) -> Option<StoredGameServer> {
+        let mut ret = None;
+
+        self.servers.rcu(|servers| {
+            let mut servers = (**servers).clone();
+
+            ret = servers
+                .iter_mut()
+                .find(|s| s.qclient.connection_id == server.connection_id && !s.is_disconnected())
+                .map(|s| {
+                    s.disconnected_at = Some(Instant::now());
+                    s.clone()
+                });
+
+            servers
+        });
+
+        ret
+    }
+
+    /// Removes a server that was detached at the given time, if it has not reconnected since
+    pub fn remove_detached(&self, string_id: &str, since: Instant) -> Option<StoredGameServer> {
+        let mut ret = None;
+
+        self.servers.rcu(|servers| {
+            let mut servers = (**servers).clone();
+
+            ret = servers
+                .iter()
+                .position(|s| {
+                    s.data.string_id.as_str() == string_id && s.disconnected_at == Some(since)
+                })
+                .map(|pos| servers.remove(pos));
+
+            servers
+        });
+
+        ret
+    }
+
+    /// Re-attaches a reconnecting server to its previous entry, if one with the same string ID is
+    /// waiting for a reconnect. Returns the server ID and whether its public data has changed.
+    pub fn reattach_server(
+        &self,
+        server: Arc<ClientState<GameServerHandler>>,
+        data: &GameServerData,
+    ) -> Option<(u8, bool)> {
+        let mut ret = None;
+
+        self.servers.rcu(|servers| {
+            let mut servers = (**servers).clone();
+
+            ret = servers
+                .iter_mut()
+                .find(|s| s.data.string_id == data.string_id && s.is_disconnected())
+                .map(|s| {
+                    let changed = s.data.address != data.address
+                        || s.data.name != data.name
+                        || s.data.region != data.region;
+
+                    s.qclient = server.clone();
+                    s.connected_at = Instant::now();
+                    s.disconnected_at = None;
+                    // all rooms are going to be announced to the server again
+                    s.room_count.store(0, Ordering::Relaxed);
+                    s.data = GameServerData { id: s.data.id, ..data.clone() };
+
+                    (s.data.id, changed)
+                });
+
+            servers
+        });
+
+        ret
+    }
+
     pub fn servers(&self) -> Arc<Vec<StoredGameServer>> {
         self.servers.load_full()
     }
 
+    /// Returns whether the server exists and is currently connected
     pub fn has_server(&self, id: u8) -> bool {
-        self.servers.load().iter().any(|s| s.data.id == id)
+        self.servers.load().iter().any(|s| s.data.id == id && !s.is_disconnected())
+    }
+
+    /// Returns whether the server hosts at least `max_rooms` rooms, `false` if the server does not exist
+    pub fn is_server_full(&self, id: u8, max_rooms: u32) -> bool {
+        self.servers.load().iter().any(|s| s.data.id == id && s.is_full(max_rooms))
+    }
+
+    /// Corrects the room counts of connected servers to match the actual amount of rooms
+    /// they are hosting (keyed by server ID), returns how many servers had to be corrected
+    pub fn reconcile_room_counts(&self, actual: &FxHashMap<u8, u32>) -> usize {
+        let mut fixed = 0;
+
+        for server in self.servers.load().iter().filter(|s| !s.is_disconnected()) {
+            let expected = actual.get(&server.data.id).copied().unwrap_or(0);
+            let count = server.room_count.swap(expected, Ordering::Relaxed);
+
+            if count != expected {
+                warn!(
+                    "Game server {} ({}) had a room count of {count}, expected {expected}",
+                    server.data.string_id, server.data.id
+                );
+                fixed += 1;
+            }
+        }
+
+        fixed
     }
 
     pub async fn notify_room_created(
@@ -156,6 +300,7 @@ impl GameServerManager {
         })?;
 
         server.qclient.send_data_bufkind(buf);
+        server.room_count.fetch_add(1, Ordering::Relaxed);
 
         // wait up to 5 seconds for a response from the game server
 
@@ -197,6 +342,9 @@ impl GameServerManager {
         })?;
 
         server.qclient.send_data_bufkind(buf);
+        let _ = server
+            .room_count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| Some(c.saturating_sub(1)));
 
         // we don't wait for an ack, that doesn't matter to us
 
@@ -258,6 +406,19 @@ impl GameServerManager {
         self.notify_all_servers(buf)
     }
 
+    /// Returns connected servers that have not sent anything in the given amount of time
+    pub fn unresponsive_servers(
+        &self,
+        timeout: Duration,
+    ) -> Vec<Arc<ClientState<GameServerHandler>>> {
+        self.servers
+            .load()
+            .iter()
+            .filter(|s| !s.is_disconnected() && s.last_seen().elapsed() >= timeout)
+            .map(|s| s.qclient.clone())
+            .collect()
+    }
+
     fn notify_all_servers_data(&self, data: SrvUserData) -> Result<(), GameServerError> {
         let buf = data::encode_message_unsafe!(self, 128, msg => {
             let notif = msg.init_notify_user_data();
@@ -5,14 +5,16 @@ use thiserror::Error;
 
 use crate::{
     auth::AuthModule,
+    core::config::NoticeAudience,
     credits::CreditsModule,
     rooms::RoomModule,
     users::{
-        self, DatabaseError, PunishUserError, UserPunishment, UserPunishmentType, UsersModule,
+        self, DatabaseError, DbUser, PunishUserError, UserPunishment, UserPunishmentType,
+        UsersModule,
     },
 };
 
-use super::{ConnectionHandler, util::*};
+use super::{ConnectionHandler, DisconnectReason, util::*};
 
 pub enum ActionType {
     Kick,
@@ -106,6 +108,17 @@ impl ConnectionHandler {
         self.send_admin_result(client, Ok::<(), &str>(()))
     }
 
+    fn send_notice_delivered(&self, client: &ClientStateHandle, count: usize) -> HandlerResult<()> {
+        // the admin result has no message field, so report the count in a system notice
+        self.send_admin_ok(client)?;
+
+        let msg =
+            format!("Notice delivered to {count} player{}", if count == 1 { "" } else { "s" });
+        client.send_data_bufkind(self.make_notice_buf(None, &msg, false, false)?);
+
+        Ok(())
+    }
+
     pub fn send_admin_db_result<E: Display>(
         &self,
         client: &ClientStateHandle,
@@ -185,7 +198,7 @@ impl ConnectionHandler {
         let users = self.module::<UsersModule>();
         let account_id = target.account_id();
 
-        target.disconnect(format!("Kicked by moderator: {reason}"));
+        self.disconnect_client(target, DisconnectReason::Kicked(reason.to_owned()));
         let _ = self.game_server_manager.notify_user_kicked(account_id).await;
 
         if do_log {
@@ -253,7 +266,7 @@ impl ConnectionHandler {
                 out
             })
         } else if level_id != 0 {
-            self.clients.collect_all_pred(|c| c.session_id().level_id() == level_id)
+            self.clients_on_level(level_id)
         } else {
             self.send_admin_result(client, Err("no target specified"))?;
             return Ok(());
@@ -283,11 +296,12 @@ impl ConnectionHandler {
             }
         }
 
+        let count = targets.len();
         for target in targets {
             self.send_notice(client, &target, message, can_reply, show_sender)?;
         }
 
-        self.send_admin_ok(client)?;
+        self.send_notice_delivered(client, count)?;
 
         Ok(())
     }
@@ -303,7 +317,7 @@ impl ConnectionHandler {
         let count = self.send_notice_all(Some(client), message, false, false).unwrap_or(0);
         users.log_notice_everyone(client.account_id(), message, count as u32).await;
 
-        self.send_admin_ok(client)?;
+        self.send_notice_delivered(client, count)?;
 
         Ok(())
     }
@@ -392,6 +406,46 @@ impl ConnectionHandler {
         Ok(targets.len())
     }
 
+    /// Sends the scheduled notice with the given index in the config to its audience,
+    /// unless it has been disabled
+    pub fn send_scheduled_notice(&self, idx: usize) {
+        let core = self.config().core();
+        let Some(notice) = core.scheduled_notices.get(idx) else {
+            return;
+        };
+
+        if !notice.enabled || notice.message.is_empty() {
+            return;
+        }
+
+        let buf = match self.make_notice_buf(None, &notice.message, false, false) {
+            Ok(buf) => Arc::new(buf),
+            Err(e) => {
+                warn!("Failed to encode scheduled notice {idx}: {e}");
+                return;
+            }
+        };
+
+        let mut count = 0usize;
+
+        for target in self.clients.collect_all() {
+            let in_level = !target.session_id().is_zero();
+
+            let matches = match notice.audience {
+                NoticeAudience::Everyone => true,
+                NoticeAudience::InLevel => in_level,
+                NoticeAudience::InMenus => !in_level,
+            };
+
+            if matches {
+                target.send_data_bufkind(BufferKind::Reference(buf.clone()));
+                count += 1;
+            }
+        }
+
+        debug!("Sent scheduled notice {idx} to {count} players");
+    }
+
     pub async fn handle_admin_fetch_user(
         &self,
         client: &ClientStateHandle,
@@ -403,9 +457,9 @@ impl ConnectionHandler {
         let users = self.module::<UsersModule>();
 
         let result = if query_num != 0 {
-            users.get_user(query_num).await
+            users.admin_get_user(query_num).await
         } else {
-            users.query_user(query).await
+            users.admin_query_user(query).await
         };
 
         match result {
@@ -416,7 +470,7 @@ impl ConnectionHandler {
                         account_id: user.account_id,
                         found: true,
                         whitelisted: user.is_whitelisted,
-                        roles: &users.role_str_to_ids(&user.roles.unwrap_or_default()),
+                        roles: &users.role_str_to_ids(user.roles.as_deref().unwrap_or_default()),
                         active_ban: user.active_ban.as_ref(),
                         active_room_ban: user.active_room_ban.as_ref(),
                         active_mute: user.active_mute.as_ref(),
@@ -426,18 +480,62 @@ impl ConnectionHandler {
                             .unwrap_or(0),
                     },
                 )?;
+
+                self.send_fetch_details(client, &user).await?;
             }
 
             Ok(None) => {
                 self.send_fetch_response(client, FetchResponse::default())?;
             }
 
-            Err(e) => self.send_admin_result(client, Err(e.to_string()))?,
+            Err(e) => self.send_admin_db_result(client, Err(e))?,
         };
 
         Ok(())
     }
 
+    /// Sends the details of a fetched user that the fetch response has no fields for,
+    /// as a system notice. Nothing is sent if there is nothing to show.
+    async fn send_fetch_details(
+        &self,
+        client: &ClientStateHandle,
+        user: &DbUser,
+    ) -> HandlerResult<()> {
+        let mut lines = Vec::new();
+
+        if let Some(note) = user.note.as_deref().filter(|n| !n.is_empty()) {
+            lines.push(format!("Note: {note}"));
+        }
+
+        // newest first, the stored history is already capped in length
+        match self.module::<UsersModule>().get_username_history(user.account_id).await {
+            Ok(history) if !history.is_empty() => {
+                let names: Vec<&str> = history.iter().map(|e| e.username.as_str()).collect();
+                lines.push(format!("Previous usernames: {}", names.join(", ")));
+            }
+
+            Ok(_) => {}
+
+            Err(e) => warn!("Failed to fetch username history of {}: {e}", user.account_id),
+        }
+
+        // the stored value is only flushed periodically, so prefer showing that they are online
+        if self.find_client(user.account_id).is_some() {
+            lines.push("Last seen: online now".to_owned());
+        } else if let Some(ts) = user.last_seen {
+            let formatted = time_format::strftime_utc("%Y-%m-%d %H:%M UTC", ts.get())
+                .unwrap_or_else(|_| "unknown".to_owned());
+            lines.push(format!("Last seen: {formatted}"));
+        }
+
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        client.send_data_bufkind(self.make_notice_buf(None, &lines.join("\n"), false, false)?);
+        Ok(())
+    }
+
     fn send_fetch_response(
         &self,
         client: &ClientStateHandle,
@@ -892,10 +990,20 @@ impl ConnectionHandler {
     ) -> HandlerResult<()> {
         self.must_be_able(client, ActionType::Kick)?;
 
+        let room = self.module::<RoomModule>().get_room(room_id);
+
         self.send_admin_result(
             client,
             match self.close_room_by_id(room_id).await {
-                Ok(true) => Ok(()),
+                Ok(true) => {
+                    if let Some(room) = room {
+                        self.module::<UsersModule>()
+                            .log_close_room(client.account_id(), room_id, &room.name, room.owner())
+                            .await;
+                    }
+
+                    Ok(())
+                }
                 Ok(false) => Err("room does not exist".to_owned()),
                 Err(e) => Err(e.to_string()),
             },
@@ -86,13 +86,6 @@ impl ClientStore {
         self.collect_pred(|client| client.authorized(), None)
     }
 
-    pub fn collect_all_pred<F: Fn(&ClientStateHandle) -> bool>(
-        &self,
-        predicate: F,
-    ) -> Vec<ClientStateHandle> {
-        self.collect_pred(predicate, None)
-    }
-
     pub fn collect_pred<F: Fn(&ClientStateHandle) -> bool>(
         &self,
         predicate: F,
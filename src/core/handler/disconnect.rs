@@ -0,0 +1,70 @@
+use std::{borrow::Cow, fmt::Display};
+
+use super::{ConnectionHandler, util::*};
+
+/// Why the server is closing a client's connection
+#[derive(Debug, Clone)]
+pub enum DisconnectReason {
+    /// The user has an active ban
+    Banned,
+    /// Kicked by a moderator, with the given reason
+    Kicked(String),
+    /// The same account logged in from a different location
+    DuplicateLogin,
+    /// Too many room invites sent in a short period
+    InviteSpam,
+    /// Too many events sent in a short period
+    EventSpam,
+    /// A game server tried to connect to the client port
+    WrongPort,
+    /// The client did not log in within the configured timeout
+    LoginTimeout,
+}
+
+impl DisconnectReason {
+    pub fn message(&self) -> Cow<'static, str> {
+        match self {
+            Self::Banned => Cow::Borrowed("user is banned"),
+            Self::Kicked(reason) => Cow::Owned(format!("Kicked by moderator: {reason}")),
+            Self::DuplicateLogin => Cow::Borrowed(
+                "Duplicate login detected, the same account logged in from a different location",
+            ),
+            Self::InviteSpam => Cow::Borrowed(
+                "Too many invites sent in a short period, please stop spamming invites",
+            ),
+            Self::EventSpam => Cow::Borrowed("Event rate limit exceeded"),
+            Self::WrongPort => Cow::Borrowed(
+                "This port accepts client connections, not game server connections. You likely input the wrong port as part of the 'central_server_url' option in your game server configuration.",
+            ),
+            Self::LoginTimeout => Cow::Borrowed("Timed out waiting for login"),
+        }
+    }
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Banned => f.write_str("banned"),
+            Self::Kicked(_) => f.write_str("kicked"),
+            Self::DuplicateLogin => f.write_str("duplicate_login"),
+            Self::InviteSpam => f.write_str("invite_spam"),
+            Self::EventSpam => f.write_str("event_spam"),
+            Self::WrongPort => f.write_str("wrong_port"),
+            Self::LoginTimeout => f.write_str("login_timeout"),
+        }
+    }
+}
+
+impl ConnectionHandler {
+    /// Disconnects the client, logging the reason
+    pub fn disconnect_client(&self, client: &ClientState<Self>, reason: DisconnectReason) {
+        info!(
+            "[{} @ {}] disconnecting client, reason={}",
+            client.account_id(),
+            client.address,
+            reason
+        );
+
+        client.disconnect(reason.message());
+    }
+}
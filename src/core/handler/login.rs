@@ -1,4 +1,4 @@
-use std::{borrow::Cow, sync::atomic::Ordering};
+use std::sync::{Arc, atomic::Ordering};
 
 use crypto_secretbox::{KeyInit, aead::AeadMutInPlace};
 use server_shared::events::EventEncoder;
@@ -21,7 +21,7 @@ use crate::analytics::{AnalyticsModule, LoginEvent};
 #[cfg(feature = "discord")]
 use crate::discord::DiscordModule;
 
-use super::{ConnectionHandler, util::*};
+use super::{ConnectionHandler, DisconnectReason, util::*};
 
 #[derive(Debug, Error)]
 enum UidentDecodeError {
@@ -146,6 +146,8 @@ impl ConnectionHandler {
         // query the database to check the user's data
         let user = match users.get_user(data.account_id).await {
             Ok(user) => user,
+            // without a database, let everyone in as a regular user
+            Err(_) if users.is_degraded() => None,
             Err(e) => {
                 warn!("[{}] failed to get user data: {}", client.address, e);
                 return self.on_login_failed(client, data::LoginFailedReason::InternalDbError);
@@ -184,6 +186,8 @@ impl ConnectionHandler {
             )?);
         }
 
+        let mut is_new_user = false;
+
         if let Some(user) = user {
             // do some checks
 
@@ -191,7 +195,7 @@ impl ConnectionHandler {
                 && username.as_str() != data.username.as_str()
             {
                 // update the username in the database
-                let _ = users.update_username(data.account_id, &data.username).await;
+                let _ = users.update_username(data.account_id, username, &data.username).await;
             }
 
             if let Some(uident) = uident.as_ref() {
@@ -222,11 +226,25 @@ impl ConnectionHandler {
             client.set_discord_linked(user.discord_id.is_some());
         } else {
             client.set_role(users.compute_from_roles(data.account_id, std::iter::empty()));
+
+            // user has no entry yet, create one if enabled
+            if users.config().create_users_on_login {
+                match users
+                    .create_user_on_login(data.account_id, &data.username, &login_data.icons)
+                    .await
+                {
+                    Ok(is_new) => is_new_user = is_new,
+                    Err(_) if users.is_degraded() => {}
+                    Err(e) => warn!("[{}] failed to create user entry: {e}", client.address),
+                }
+            }
         }
 
         // check potential alt account
-        if let Some(uident) = uident.as_ref() {
-            let accounts = match users.get_accounts_for_uident(uident, false).await {
+        if let Some(uident) = uident.as_ref()
+            && !users.is_degraded()
+        {
+            let mut accounts = match users.get_accounts_for_uident(uident, false).await {
                 Ok(x) => x,
                 Err(e) => {
                     warn!("[{}] failed to get alt accounts: {}", client.address, e);
@@ -234,7 +252,50 @@ impl ConnectionHandler {
                 }
             };
 
+            // accounts that staff marked as belonging to the same person are not suspicious
+            if let Err(e) = users.filter_linked_accounts(data.account_id, &mut accounts).await {
+                warn!("[{}] failed to get linked accounts: {e}", client.address);
+            }
+
             if accounts.iter().any(|&id| id != data.account_id) {
+                // optionally block the login if any of the other accounts is banned
+                if users.enforce_uident_ban_evasion() && !client.can_moderate() {
+                    match users.find_banned_account(&accounts, data.account_id).await {
+                        Ok(Some(ban)) => {
+                            let banned_id = ban.account_id;
+                            warn!(
+                                "[{}] {} ({}) rejected for ban evasion, shares uident with {}",
+                                client.address, data.username, data.account_id, banned_id
+                            );
+
+                            let _ = users.insert_uident(data.account_id, uident).await;
+
+                            #[cfg(feature = "discord")]
+                            if let Some(discord) = discord {
+                                discord.send_ban_evasion_alert(
+                                    &data.username,
+                                    data.account_id,
+                                    banned_id,
+                                );
+                            }
+
+                            // forward the original ban, so the client shows its reason and expiry
+                            // rather than what looks like a permanent ban of this account
+                            let reason = format!(
+                                "Ban evasion, another account of yours is banned: {}",
+                                ban.reason
+                            );
+                            return self.send_banned(client, &reason, ban.expires_at);
+                        }
+
+                        Ok(None) => {}
+
+                        Err(e) => {
+                            warn!("[{}] failed to check for ban evasion: {e}", client.address)
+                        }
+                    }
+                }
+
                 match users.insert_uident(data.account_id, uident).await {
                     Ok(true) => {
                         // notify on discord if any of the accounts have active punishments
@@ -275,6 +336,14 @@ impl ConnectionHandler {
         client.set_icons(login_data.icons);
         client.set_account_data(data.clone());
 
+        // if the account is already logged in elsewhere, remove the old client from its room and
+        // session right away, rather than waiting for it to disconnect, so it isn't counted twice
+        if let Some(old_client) = self.find_client(data.account_id)
+            && !Arc::ptr_eq(&old_client, client)
+        {
+            self.teardown_client(&old_client).await;
+        }
+
         // insert into the clients map
         if let Some(old_client) = self.clients.insert(data.account_id, &data.username, client) {
             debug!(
@@ -282,9 +351,7 @@ impl ConnectionHandler {
                 data.account_id, old_client.address, client.address
             );
             // there already was a client with this account ID, disconnect them
-            old_client.disconnect(Cow::Borrowed(
-                "Duplicate login detected, the same account logged in from a different location",
-            ));
+            self.disconnect_client(&old_client, DisconnectReason::DuplicateLogin);
         }
 
         // if the username has disallowed words, send a discord notification
@@ -383,6 +450,45 @@ impl ConnectionHandler {
 
         client.send_data_bufkind(buf);
 
+        // send the message of the day, if there is one
+        let core = self.config().core();
+        if !core.motd.is_empty() {
+            client.send_data_bufkind(self.make_notice_buf(None, &core.motd, false, false)?);
+        }
+
+        if is_new_user {
+            self.send_welcome(client, &data)?;
+        }
+
+        Ok(())
+    }
+
+    fn send_welcome(
+        &self,
+        client: &ClientStateHandle,
+        data: &ClientAccountData,
+    ) -> HandlerResult<()> {
+        let users = self.module::<UsersModule>();
+        let config = users.config();
+
+        debug!("[{}] {} ({}) is a new user", client.address, data.username, data.account_id);
+
+        if !config.welcome_message.is_empty() {
+            client.send_data_bufkind(self.make_notice_buf(
+                None,
+                &config.welcome_message,
+                false,
+                false,
+            )?);
+        }
+
+        #[cfg(feature = "discord")]
+        if config.welcome_discord_alert
+            && let Some(discord) = self.opt_module::<DiscordModule>()
+        {
+            discord.send_welcome_alert(&data.username, data.account_id);
+        }
+
         Ok(())
     }
 
@@ -392,6 +498,8 @@ impl ConnectionHandler {
         client: &ClientState<Self>,
         reason: data::LoginFailedReason,
     ) -> HandlerResult<()> {
+        self.record_login_failure(reason);
+
         let buf = data::encode_message!(self, 40, msg => {
             let mut login_failed = msg.reborrow().init_login_failed();
             login_failed.set_reason(reason);
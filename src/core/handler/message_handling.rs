@@ -1,8 +1,8 @@
-use std::{borrow::Cow, num::NonZeroI64};
+use std::num::NonZeroI64;
 
 use rustc_hash::FxHashSet;
 use server_shared::{
-    UserSettings,
+    UserSettings, UsernameString,
     data::{PlayerIconData, SRVC_MAGIC},
     encoding::{DataDecodeError, heapless_str_from_reader},
     qunet::{buffers::ByteWriter, message::MsgData, server::Server as QunetServer},
@@ -13,8 +13,9 @@ use tracing::{debug, warn};
 use crate::{
     auth::{ClientAccountData, LoginKind},
     core::{
+        client_data::LimitedMessage,
         data::{self, decode_message_match},
-        handler::{ClientStateHandle, ConnectionHandler, util::HandlerResult},
+        handler::{ClientStateHandle, ConnectionHandler, DisconnectReason, util::HandlerResult},
     },
     rooms::RoomSettings,
     users::{ComputedRole, UsersModule},
@@ -32,7 +33,7 @@ impl ConnectionHandler {
         if data.len() >= 8 {
             let magic = u64::from_le_bytes(data[..8].try_into().unwrap());
             if magic == SRVC_MAGIC {
-                client.disconnect("This port accepts client connections, not game server connections. You likely input the wrong port as part of the 'central_server_url' option in your game server configuration.");
+                self.disconnect_client(client, DisconnectReason::WrongPort);
                 debug!(
                     "[{} @ {}] disconnected client that sent srvc magic",
                     client.connection_id, client.address
@@ -41,6 +42,10 @@ impl ConnectionHandler {
             }
         }
 
+        if !self.check_global_message_limit(client) {
+            return;
+        }
+
         let result = decode_message_match!(self, data, unpacked_data, {
             Login(message) => {
                 let data = decode_login_data(message)?;
@@ -78,6 +83,10 @@ impl ConnectionHandler {
             },
 
             RequestPlayerCounts(message) => {
+                if !self.check_message_limit(client, LimitedMessage::PlayerCounts) {
+                    return Ok(Ok(()));
+                }
+
                 let levels = message.get_levels()?;
                 let mut out_levels = heapless::Vec::<u64, 128>::new();
 
@@ -91,19 +100,31 @@ impl ConnectionHandler {
             },
 
             RequestLevelList(_msg) => {
+                if !self.check_message_limit(client, LimitedMessage::LevelList) {
+                    return Ok(Ok(()));
+                }
+
                 unpacked_data.reset(); // free up memory
 
                 self.handle_request_level_list(client).await
             },
 
             RequestGlobalPlayerList(msg) => {
-                let name_filter = heapless_str_from_reader::<32>(msg.get_name_filter()?)?;
+                if !self.check_message_limit(client, LimitedMessage::GlobalPlayerList) {
+                    return Ok(Ok(()));
+                }
+
+                let name_filter = filter_from_str::<32>(msg.get_name_filter()?.to_str()?);
                 unpacked_data.reset(); // free up memory
 
                 self.handle_request_global_player_list(client, &name_filter).await
             },
 
             CreateRoom(message) => {
+                if !self.check_message_limit(client, LimitedMessage::CreateRoom) {
+                    return Ok(Ok(()));
+                }
+
                 let name = heapless_str_from_reader::<32>(message.get_name()?)?;
                 let settings = RoomSettings::from_reader(message.get_settings()?)?;
                 let passcode = message.get_passcode();
@@ -142,7 +163,11 @@ impl ConnectionHandler {
             },
 
             RequestRoomPlayers(msg) => {
-                let name_filter = heapless_str_from_reader::<32>(msg.get_name_filter()?)?;
+                if !self.check_message_limit(client, LimitedMessage::RoomPlayers) {
+                    return Ok(Ok(()));
+                }
+
+                let name_filter = filter_from_str::<32>(msg.get_name_filter()?.to_str()?);
 
                 unpacked_data.reset(); // free up memory
 
@@ -150,7 +175,11 @@ impl ConnectionHandler {
             },
 
             RequestRoomList(msg) => {
-                let name_filter = heapless_str_from_reader::<32>(msg.get_name_filter()?)?;
+                if !self.check_message_limit(client, LimitedMessage::RoomList) {
+                    return Ok(Ok(()));
+                }
+
+                let name_filter = filter_from_str::<32>(msg.get_name_filter()?.to_str()?);
                 let page = msg.get_page();
 
                 unpacked_data.reset(); // free up memory
@@ -312,6 +341,10 @@ impl ConnectionHandler {
             },
 
             AdminNoticeEveryone(message) => {
+                if !self.check_message_limit(client, LimitedMessage::NoticeEveryone) {
+                    return Ok(Ok(()));
+                }
+
                 let message = message.get_message()?.to_str()?;
                 self.handle_admin_notice_everyone(client, message).await
             },
@@ -511,6 +544,69 @@ impl ConnectionHandler {
         }
     }
 
+    /// Returns `false` if the message should be dropped due to the client sending too many messages per second.
+    /// Staff are only limited by the transport, so bulk moderation actions don't get throttled.
+    fn check_global_message_limit(&self, client: &ClientStateHandle) -> bool {
+        let limits = &self.config().core().message_rate_limits;
+
+        if client.can_moderate() || client.try_consume_any_message(limits.per_second) {
+            return true;
+        }
+
+        if limits.warn_on_drop {
+            warn!(
+                "[{} @ {}] dropping message, per-second limit exceeded",
+                client.account_id(),
+                client.address
+            );
+        }
+
+        false
+    }
+
+    /// Returns `false` if the message should be dropped due to the client exceeding its rate limit
+    fn check_message_limit(&self, client: &ClientStateHandle, kind: LimitedMessage) -> bool {
+        if self.module::<UsersModule>().is_trusted(client.account_id()) {
+            return true;
+        }
+
+        let limits = &self.config().core().message_rate_limits;
+
+        let per_minute = match kind {
+            LimitedMessage::CreateRoom => limits.create_room,
+            LimitedMessage::NoticeEveryone => limits.notice_everyone,
+            LimitedMessage::GlobalPlayerList => limits.global_player_list,
+            LimitedMessage::RoomList => limits.room_list,
+            LimitedMessage::PlayerCounts => limits.player_counts,
+            LimitedMessage::LevelList => limits.level_list,
+            LimitedMessage::RoomPlayers => limits.room_players,
+        };
+
+        let per_minute = if client.can_moderate() {
+            if limits.staff_multiplier == 0 {
+                return true;
+            }
+
+            per_minute.saturating_mul(limits.staff_multiplier)
+        } else {
+            per_minute
+        };
+
+        if client.try_consume_message(kind, per_minute, limits.max_burst) {
+            return true;
+        }
+
+        if limits.warn_on_drop {
+            warn!(
+                "[{} @ {}] dropping {kind:?} message, rate limit exceeded",
+                client.account_id(),
+                client.address
+            );
+        }
+
+        false
+    }
+
     pub fn send_banned(
         &self,
         client: &ClientStateHandle,
@@ -524,7 +620,7 @@ impl ConnectionHandler {
         })?;
 
         client.send_data_bufkind(buf);
-        client.disconnect(Cow::Borrowed("user is banned"));
+        self.disconnect_client(client, DisconnectReason::Banned);
 
         Ok(())
     }
@@ -613,7 +709,21 @@ fn decode_login_data<'a>(
     use server_shared::schema::main::login_message::Which;
 
     let account_id = message.get_account_id();
-    let icons = PlayerIconData::from_reader(message.get_icons()?)?;
+
+    // icons are optional, a client can always set them later with UpdateOwnData
+    let icons = if message.has_icons() {
+        let decode = || -> Result<_, DataDecodeError> {
+            Ok(PlayerIconData::from_reader(message.get_icons()?)?)
+        };
+
+        decode().unwrap_or_else(|e| {
+            warn!("[{account_id}] failed to decode icons at login, using defaults: {e}");
+            PlayerIconData::default()
+        })
+    } else {
+        PlayerIconData::default()
+    };
+
     let uident = if message.has_uident() { Some(message.get_uident()?) } else { None };
     let settings = UserSettings::from_reader(message.get_settings()?);
 
@@ -624,7 +734,7 @@ fn decode_login_data<'a>(
 
         Which::Plain(m) => {
             let data = m?;
-            let username = heapless_str_from_reader(data.get_username()?)?;
+            let username = username_from_str(account_id, data.get_username()?.to_str()?);
             let user_id = data.get_user_id();
 
             LoginKind::Plain(ClientAccountData { account_id, user_id, username })
@@ -668,3 +778,110 @@ fn decode_login_data<'a>(
         event_dict,
     })
 }
+
+/// The username length limit is a storage choice of the server, so instead of failing the login,
+/// names that are too long get truncated
+fn username_from_str(account_id: i32, name: &str) -> UsernameString {
+    if let Ok(username) = UsernameString::try_from(name) {
+        return username;
+    }
+
+    warn!("[{account_id}] username '{name}' is too long, truncating");
+
+    let mut username = UsernameString::new();
+    for c in name.chars() {
+        if username.push(c).is_err() {
+            break;
+        }
+    }
+
+    username
+}
+
+/// Search filters don't need to be exact, so ones that are too long are truncated
+/// instead of failing the whole request
+fn filter_from_str<const N: usize>(filter: &str) -> heapless::String<N> {
+    let mut out = heapless::String::new();
+
+    for c in filter.chars() {
+        if out.push(c).is_err() {
+            debug!("truncating over-length filter '{filter}' to {N} bytes");
+            break;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use capnp::message::ReaderOptions;
+
+    use super::*;
+
+    fn settings_from_bytes(bytes: &[u8]) -> Option<UserSettings> {
+        let message =
+            capnp::serialize::read_message_from_flat_slice(&mut &bytes[..], ReaderOptions::new())
+                .ok()?;
+        let root = message.get_root::<data::user_settings::Reader<'_>>().ok()?;
+
+        Some(UserSettings::from_reader(root))
+    }
+
+    #[test]
+    fn filter_empty() {
+        assert!(filter_from_str::<32>("").is_empty());
+    }
+
+    #[test]
+    fn filter_within_limit_is_kept() {
+        let filter = "a".repeat(32);
+        assert_eq!(filter_from_str::<32>(&filter).as_str(), filter);
+    }
+
+    #[test]
+    fn filter_over_limit_is_truncated() {
+        let filter = "b".repeat(100);
+        assert_eq!(filter_from_str::<32>(&filter).as_str(), &filter[..32]);
+    }
+
+    #[test]
+    fn filter_truncated_at_char_boundary() {
+        // 31 ASCII bytes, then a 2-byte character that would end past the limit
+        let filter = format!("{}é", "c".repeat(31));
+        assert_eq!(filter_from_str::<32>(&filter).as_str(), &filter[..31]);
+
+        // 4-byte characters, only 8 fit
+        let filter = "🦀".repeat(10);
+        let out = filter_from_str::<32>(&filter);
+        assert_eq!(out.as_str(), "🦀".repeat(8));
+        assert_eq!(out.len(), 32);
+    }
+
+    #[test]
+    fn user_settings_all_set() {
+        let mut message = capnp::message::Builder::new_default();
+        let mut builder = message.init_root::<data::user_settings::Builder<'_>>();
+        builder.set_hide_in_menus(true);
+        builder.set_hide_roles(true);
+        builder.set_disable_notices(true);
+
+        let reader = message.into_reader();
+        let settings = UserSettings::from_reader(reader.get_root().unwrap());
+
+        assert!(settings.hide_in_menus);
+        assert!(settings.hide_roles);
+        assert!(settings.disable_notices);
+    }
+
+    #[test]
+    fn user_settings_null_root_is_default() {
+        // one segment of one word, holding a null root pointer, like a client that sends no settings
+        let bytes = [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let settings = settings_from_bytes(&bytes).unwrap();
+
+        assert!(!settings.hide_in_menus);
+        assert!(!settings.hide_roles);
+        assert!(!settings.disable_notices);
+    }
+}
@@ -12,7 +12,7 @@ use crate::{
     users::{LinkedDiscordAccount, UsersModule},
 };
 
-use super::{ConnectionHandler, util::*};
+use super::{ConnectionHandler, DisconnectReason, util::*};
 
 pub enum HandleEventError {
     RateLimit,
@@ -64,9 +64,11 @@ impl ConnectionHandler {
         for &sess in sessions {
             if let Some(ent) = self.all_levels.get(&sess)
                 && !ent.is_hidden
-                && ent.player_count > 0
             {
-                let _ = out_vals.push((sess, ent.player_count as u16));
+                let count = self.session_client_count(sess);
+                if count > 0 {
+                    let _ = out_vals.push((sess, count.min(u16::MAX as usize) as u16));
+                }
             }
         }
 
@@ -329,6 +331,11 @@ impl ConnectionHandler {
     ) -> HandlerResult<()> {
         must_auth(client)?;
 
+        if let Some(p) = client.active_mute.lock().as_ref() {
+            // user is muted, don't let them reply to notices
+            return self.send_muted(client, &p.reason, p.expires_at);
+        }
+
         let res = self.notice_reply_inner(client, target_user, message).await;
 
         let buf = data::encode_message!(self, 512, msg => {
@@ -349,6 +356,11 @@ impl ConnectionHandler {
         target_user: i32,
         message: &str,
     ) -> Result<(), &'static str> {
+        let cooldown = self.module::<UsersModule>().reply_cooldown(client.role().as_ref());
+        if !client.try_notice_reply(cooldown) {
+            return Err("you are sending replies too fast, please wait a bit");
+        }
+
         let Some(target) = self.find_client(target_user) else {
             debug!("{} could not reply to {target_user}, target not found", client.account_id());
             return Err("user went offline");
@@ -436,7 +448,7 @@ impl ConnectionHandler {
                         client.address
                     );
 
-                    client.disconnect("Event rate limit exceeded");
+                    self.disconnect_client(client, DisconnectReason::EventSpam);
                     return Ok(());
                 }
 
@@ -1,15 +1,18 @@
 use std::{
+    collections::BTreeMap,
     net::SocketAddr,
     path::Path,
     sync::{
         Arc, OnceLock,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
     time::{Duration, Instant, SystemTime},
 };
 
 use dashmap::DashMap;
+use nohash_hasher::IntSet;
 use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
 use serde::Serialize;
 use server_shared::{
     SessionId,
@@ -29,7 +32,7 @@ use crate::{
     auth::{ArgonConnectionState, AuthModule},
     core::{
         client_data::ClientData,
-        config::Config,
+        config::{Config, ConnectionLogging},
         data::{self},
         event_worker::EventWorker,
         game_server::{GameServerHandler, GameServerManager, StoredGameServer},
@@ -42,6 +45,7 @@ use crate::{
 
 mod admin;
 mod client_store;
+mod disconnect;
 #[cfg(feature = "featured-levels")]
 mod featured;
 mod login;
@@ -50,6 +54,7 @@ mod misc;
 mod rooms;
 mod session;
 mod util;
+pub use disconnect::DisconnectReason;
 pub use message_handling::LoginData;
 use util::*;
 pub use util::{ClientState, ClientStateHandle, WeakClientStateHandle};
@@ -75,7 +80,19 @@ pub struct ConnectionHandler {
 
     clients: ClientStore,
     all_levels: DashMap<u64, LevelEntry>,
+    /// Level counts that did not match on the last reconcile, as (stored, actual) counts
+    level_count_suspects: Mutex<FxHashMap<u64, (u32, u32)>>,
+    /// Level ID -> account IDs of everyone currently playing it, in any room
+    level_clients: DashMap<i32, IntSet<i32>>,
+    /// Session ID -> account IDs of everyone currently in that session
+    session_clients: DashMap<u64, FxHashSet<i32>>,
     refuse_connections: AtomicBool,
+    /// Failed login counts since startup, keyed by the name of the `LoginFailedReason`
+    login_failures: DashMap<String, AtomicU64>,
+    /// Failed message encodes since startup, keyed by the call site of the encode macro
+    encode_failures: DashMap<&'static str, AtomicU64>,
+    /// Incoming connections since startup, used for sampling connection logs
+    connection_counter: AtomicU64,
 
     event_string_cache: EventStringCache,
     event_worker: EventWorker,
@@ -109,6 +126,21 @@ impl AppHandler for ConnectionHandler {
             let rooms = h.module::<RoomModule>();
             info!(" - Room count: {}", rooms.get_room_count());
 
+            let auth = h.module::<AuthModule>();
+            if auth.verification_enabled() {
+                if auth.argon_connected() {
+                    info!(" - Argon: connected");
+                } else {
+                    warn!(" - Argon: disconnected, logins will fail until it is reachable");
+                }
+            }
+
+            let failures = h.login_failure_counts();
+            if !failures.is_empty() {
+                let summary: Vec<_> = failures.iter().map(|(r, c)| format!("{r}: {c}")).collect();
+                info!(" - Failed logins: {}", summary.join(", "));
+            }
+
             // vacuum invalid clients
             let removed_clients = h.clients.vacuum();
             if removed_clients > 0 {
@@ -119,6 +151,51 @@ impl AppHandler for ConnectionHandler {
             }
         });
 
+        for (idx, notice) in self.config.core().scheduled_notices.iter().enumerate() {
+            let interval = Duration::from_secs(notice.interval_secs.max(60));
+
+            server.schedule(interval, move |server| async move {
+                server.handler().send_scheduled_notice(idx);
+            });
+        }
+
+        // fix up any drift in level player counts
+        let reconcile_secs = self.config.core().level_count_reconcile_secs;
+        if reconcile_secs > 0 {
+            server.schedule(Duration::from_secs(reconcile_secs), |server| async move {
+                let h = server.handler();
+                h.reconcile_level_players();
+                h.vacuum_session_index();
+            });
+        }
+
+        // disconnect clients that never logged in, the timeout is re-read so it can be reloaded
+        server.schedule(Duration::from_secs(5), |server| async move {
+            server.handler().kick_pending_logins();
+        });
+
+        // make sure game servers are still responsive, a hung server would make its rooms unreachable
+        let core = self.config.core();
+        if core.gs_liveness_check_interval_secs > 0 && core.gs_idle_timeout_secs > 0 {
+            server.schedule(
+                Duration::from_secs(core.gs_liveness_check_interval_secs),
+                |server| async move {
+                    server.handler().check_game_servers().await;
+                },
+            );
+        }
+
+        // fix up any drift in the room counts of game servers
+        server.schedule(Duration::from_mins(10), |server| async move {
+            let h = server.handler();
+            let actual = h.module::<RoomModule>().room_counts_by_server();
+            let fixed = h.game_server_manager.reconcile_room_counts(&actual);
+
+            if fixed > 0 {
+                warn!("Corrected room counts of {fixed} game servers, this is likely a bug!");
+            }
+        });
+
         // periodically clean up stat tracker stuff if enabled
         if server.stat_tracker().is_some() {
             server.schedule(Duration::from_mins(30), |server| async move {
@@ -129,6 +206,29 @@ impl AppHandler for ConnectionHandler {
             });
         }
 
+        // SIGHUP reloads the config, same as SIGUSR2
+        #[cfg(unix)]
+        {
+            let server = server.clone();
+
+            tokio::spawn(async move {
+                use tokio::signal::unix::{SignalKind, signal};
+
+                let mut hangup = match signal(SignalKind::hangup()) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        warn!("Failed to register SIGHUP handler: {e}");
+                        return;
+                    }
+                };
+
+                while hangup.recv().await.is_some() {
+                    info!("Received SIGHUP, reloading configuration");
+                    server.handler().reload_config().await;
+                }
+            });
+        }
+
         for module in self.module_list.lock().iter() {
             module.on_launch(&server);
         }
@@ -146,13 +246,29 @@ impl AppHandler for ConnectionHandler {
         kind: &str,
     ) -> AppResult<Self::ClientData> {
         if self.server.get().is_none() {
+            warn!(
+                "Rejecting connection, server not initialized yet: connection_id={}, address={}, kind={}",
+                connection_id, address, kind
+            );
             return Err("server not initialized yet".into());
         }
 
-        info!(
-            "Client connected: connection_id={}, address={}, kind={}",
-            connection_id, address, kind
-        );
+        let core = self.config.core();
+        let log = match core.log_connections {
+            ConnectionLogging::Off => false,
+            ConnectionLogging::Sampled => {
+                let n = self.connection_counter.fetch_add(1, Ordering::Relaxed);
+                n.is_multiple_of(core.connection_log_sample_rate.max(1) as u64)
+            }
+            ConnectionLogging::All => true,
+        };
+
+        if log {
+            info!(
+                "Client connected: connection_id={}, address={}, kind={}",
+                connection_id, address, kind
+            );
+        }
 
         Ok(ClientData::default())
     }
@@ -163,13 +279,12 @@ impl AppHandler for ConnectionHandler {
         debug!("[{} @ {}] client disconnected", account_id, client.address);
 
         if account_id != 0 {
-            let rooms = self.module::<RoomModule>();
-            rooms.cleanup_player(client, &self.game_server_manager).await;
+            self.teardown_client(client).await;
 
             // remove only if the client has not been replaced by a newer login
             self.clients.remove_if_same(account_id, client);
 
-            let _ = self.handle_leave_session(client).await;
+            self.module::<UsersModule>().mark_seen(account_id);
         }
     }
 
@@ -180,6 +295,9 @@ impl AppHandler for ConnectionHandler {
         let rooms = self.module::<RoomModule>();
         rooms.cleanup_everything();
 
+        // write last seen times of everyone who was still connected
+        self.module::<UsersModule>().flush_last_seen().await;
+
         self.event_worker.abort();
 
         info!("Post-shutdown cleanup complete");
@@ -224,13 +342,48 @@ impl ConnectionHandler {
             launched_at: Instant::now(),
             clients: ClientStore::new(),
             all_levels: DashMap::new(),
+            level_count_suspects: Mutex::new(FxHashMap::default()),
+            level_clients: DashMap::new(),
+            session_clients: DashMap::new(),
             refuse_connections: AtomicBool::new(false),
+            login_failures: DashMap::new(),
+            encode_failures: DashMap::new(),
+            connection_counter: AtomicU64::new(0),
 
             event_string_cache: EventStringCache::new(),
             event_worker: EventWorker::new(),
         }
     }
 
+    /// Disconnects clients that are still not logged in after `login_timeout_secs`.
+    /// This scans every open connection, so clients that never send anything are caught too.
+    fn kick_pending_logins(&self) {
+        let timeout = Duration::from_secs(self.config.core().login_timeout_secs);
+        if timeout.is_zero() {
+            return;
+        }
+
+        let expired: Vec<ClientStateHandle> = self
+            .server()
+            .clients()
+            .into_iter()
+            .filter(|client| client.account_id() == 0 && client.connected_for() >= timeout)
+            .collect();
+
+        for client in expired {
+            self.disconnect_client(&client, DisconnectReason::LoginTimeout);
+        }
+    }
+
+    /// Removes the client from its room and session. Safe to call multiple times,
+    /// subsequent calls do nothing.
+    async fn teardown_client(&self, client: &ClientStateHandle) {
+        let rooms = self.module::<RoomModule>();
+        rooms.cleanup_player(client, &self.game_server_manager).await;
+
+        let _ = self.handle_leave_session(client).await;
+    }
+
     pub fn insert_module<T: ServerModule + ConfigurableModule + Sized>(&self, module: T) {
         self.modules.insert(module);
         let module = self.opt_module_owned::<T>().unwrap();
@@ -368,6 +521,160 @@ impl ConnectionHandler {
         });
     }
 
+    fn add_session_client(&self, session: SessionId, account_id: i32) {
+        self.session_clients.entry(session.as_u64()).or_default().insert(account_id);
+        self.level_clients.entry(session.level_id()).or_default().insert(account_id);
+    }
+
+    fn remove_session_client(&self, session: SessionId, account_id: i32) {
+        self.session_clients.remove_if_mut(&session.as_u64(), |_, clients| {
+            clients.remove(&account_id);
+            clients.is_empty()
+        });
+
+        self.level_clients.remove_if_mut(&session.level_id(), |_, clients| {
+            clients.remove(&account_id);
+            clients.is_empty()
+        });
+    }
+
+    /// Returns how many clients are currently in the given session
+    pub fn session_client_count(&self, session: u64) -> usize {
+        self.session_clients.get(&session).map_or(0, |c| c.len())
+    }
+
+    /// Returns all clients that are currently playing the given level, in any room
+    pub fn clients_on_level(&self, level_id: i32) -> Vec<ClientStateHandle> {
+        let Some(accounts) = self.level_clients.get(&level_id) else {
+            return Vec::new();
+        };
+
+        accounts
+            .iter()
+            .filter_map(|&id| self.find_client(id))
+            .filter(|c| c.session_id().level_id() == level_id)
+            .collect()
+    }
+
+    /// Recomputes player counts of all levels from the sessions of connected clients,
+    /// correcting any counts that drifted due to bugs.
+    ///
+    /// Session changes update the client and the level counts separately, so a single run can
+    /// observe one halfway through. A count is only corrected once two consecutive runs saw the
+    /// exact same mismatch, and only if it was not modified in the meantime.
+    pub fn reconcile_level_players(&self) {
+        let users = self.module::<UsersModule>();
+
+        let mut actual = FxHashMap::<u64, u32>::default();
+        for client in self.clients.collect_all() {
+            let session = client.session_id_u64();
+            if session != 0 {
+                *actual.entry(session).or_default() += 1;
+            }
+        }
+
+        // session -> (stored count, actual count), a stored count of 0 means there is no entry
+        let mut mismatches = FxHashMap::<u64, (u32, u32)>::default();
+
+        for entry in self.all_levels.iter() {
+            let count = actual.get(entry.key()).copied().unwrap_or(0);
+            if entry.player_count != count {
+                mismatches.insert(*entry.key(), (entry.player_count, count));
+            }
+        }
+
+        for (&session, &count) in &actual {
+            if !self.all_levels.contains_key(&session) {
+                mismatches.insert(session, (0, count));
+            }
+        }
+
+        let confirmed: Vec<(u64, (u32, u32))> = {
+            let mut suspects = self.level_count_suspects.lock();
+            let confirmed = mismatches
+                .iter()
+                .filter(|&(session, counts)| suspects.get(session) == Some(counts))
+                .map(|(&session, &counts)| (session, counts))
+                .collect();
+
+            *suspects = mismatches;
+            confirmed
+        };
+
+        let mut fixed = 0usize;
+
+        for (session, (stored, count)) in confirmed {
+            let corrected = if count == 0 {
+                self.all_levels.remove_if(&session, |_, e| e.player_count == stored).is_some()
+            } else if stored == 0 {
+                match self.all_levels.entry(session) {
+                    dashmap::Entry::Vacant(entry) => {
+                        let is_hidden =
+                            users.is_level_blacklisted(SessionId::from(session).level_id());
+                        entry.insert(LevelEntry { player_count: count, is_hidden });
+                        true
+                    }
+                    dashmap::Entry::Occupied(_) => false,
+                }
+            } else {
+                match self.all_levels.get_mut(&session) {
+                    Some(mut entry) if entry.player_count == stored => {
+                        entry.player_count = count;
+                        true
+                    }
+                    _ => false,
+                }
+            };
+
+            if corrected {
+                debug!("level {session} had {stored} players, expected {count}");
+                self.level_count_suspects.lock().remove(&session);
+                fixed += 1;
+            }
+        }
+
+        if fixed > 0 {
+            warn!("Corrected player counts of {fixed} levels, this is likely a bug!");
+        }
+    }
+
+    /// Removes clients from the session and level indexes that are no longer connected
+    /// or have since moved to a different session
+    pub fn vacuum_session_index(&self) {
+        let mut removed = 0usize;
+
+        self.session_clients.retain(|&session, clients| {
+            clients.retain(|&id| {
+                let valid = self.find_client(id).is_some_and(|c| c.session_id_u64() == session);
+                if !valid {
+                    debug!("removing {id} from session {session}, they are no longer in it");
+                    removed += 1;
+                }
+                valid
+            });
+
+            !clients.is_empty()
+        });
+
+        self.level_clients.retain(|&level_id, clients| {
+            clients.retain(|&id| {
+                let valid =
+                    self.find_client(id).is_some_and(|c| c.session_id().level_id() == level_id);
+                if !valid {
+                    debug!("removing {id} from level {level_id}, they are no longer on it");
+                    removed += 1;
+                }
+                valid
+            });
+
+            !clients.is_empty()
+        });
+
+        if removed > 0 {
+            warn!("Removed {removed} stale entries from the session index, this is likely a bug!");
+        }
+    }
+
     pub fn override_level_hidden(&self, session: u64, hidden: bool) -> bool {
         if let Some(mut ent) = self.all_levels.get_mut(&session) {
             ent.is_hidden = hidden;
@@ -381,6 +688,46 @@ impl ConnectionHandler {
         self.http_client.clone()
     }
 
+    pub fn record_login_failure(&self, reason: data::LoginFailedReason) {
+        self.login_failures
+            .entry(format!("{reason:?}"))
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Called by the encode macros when encoding a message fails, most likely because
+    /// the estimated capacity was too small
+    pub fn record_encode_failure(
+        &self,
+        site: &'static str,
+        estcap: usize,
+        err: &data::EncodeMessageError,
+    ) {
+        self.encode_failures.entry(site).or_default().fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(debug_assertions)]
+        warn!("Failed to encode message at {site} (estimated capacity: {estcap} bytes): {err}");
+
+        #[cfg(not(debug_assertions))]
+        let _ = (estcap, err);
+    }
+
+    /// Returns how many message encodes have failed since startup, per call site
+    pub fn encode_failure_counts(&self) -> BTreeMap<String, u64> {
+        self.encode_failures
+            .iter()
+            .map(|e| ((*e.key()).to_owned(), e.value().load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Returns how many logins have failed since startup, for every reason that occurred at least once
+    pub fn login_failure_counts(&self) -> BTreeMap<String, u64> {
+        self.login_failures
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect()
+    }
+
     pub fn get_server_health(&self) -> ServerHealth {
         let auth = self.module::<AuthModule>();
 
@@ -392,9 +739,13 @@ impl ConnectionHandler {
                 ArgonConnectionState::Disconnected => "down",
             },
             argon_connected_for: auth.argon_connected_for().map(|x| x.as_secs_f64()),
+            analytics_state: self.analytics_state(),
+            analytics_dropped_events: self.analytics_dropped_events(),
             clients: self.server().client_count(),
             rooms: self.module::<RoomModule>().get_room_count(),
             levels: self.level_count(),
+            login_failures: self.login_failure_counts(),
+            encode_failures: self.encode_failure_counts(),
 
             game_servers: self
                 .game_server_manager
@@ -404,11 +755,38 @@ impl ConnectionHandler {
                     id: s.data.string_id.as_str().to_owned(),
                     uptime: s.uptime().as_secs_f64(),
                     load: s.status_data().server_load,
+                    rooms: s.room_count(),
                 })
                 .collect(),
         }
     }
 
+    #[cfg(feature = "analytics")]
+    fn analytics_state(&self) -> &'static str {
+        use crate::analytics::{AnalyticsConnectionState, AnalyticsModule};
+
+        match self.opt_module::<AnalyticsModule>().map(|m| m.connection_state()) {
+            None | Some(AnalyticsConnectionState::Disabled) => "disabled",
+            Some(AnalyticsConnectionState::Connected) => "up",
+            Some(AnalyticsConnectionState::Disconnected) => "down",
+        }
+    }
+
+    #[cfg(not(feature = "analytics"))]
+    fn analytics_state(&self) -> &'static str {
+        "disabled"
+    }
+
+    #[cfg(feature = "analytics")]
+    fn analytics_dropped_events(&self) -> u64 {
+        self.opt_module::<crate::analytics::AnalyticsModule>().map_or(0, |m| m.dropped_events())
+    }
+
+    #[cfg(not(feature = "analytics"))]
+    fn analytics_dropped_events(&self) -> u64 {
+        0
+    }
+
     // Handling of game servers.
 
     pub fn get_game_servers(&self) -> Arc<Vec<StoredGameServer>> {
@@ -427,14 +805,94 @@ impl ConnectionHandler {
         client: Arc<ClientState<GameServerHandler>>,
         data: GameServerData,
     ) -> HandlerResult<()> {
-        self.game_server_manager.add_server(client, data);
+        // if the server is reconnecting within the grace period, keep its rooms alive
+        if let Some((server_id, changed)) =
+            self.game_server_manager.reattach_server(client.clone(), &data)
+        {
+            info!("Game server '{}' reconnected within the grace period", data.string_id);
+
+            if changed {
+                self.notify_servers_changed().await;
+            }
+
+            let server = self.server();
+            tokio::spawn(async move {
+                let handler = server.handler();
+                handler
+                    .module::<RoomModule>()
+                    .rehost_rooms_on_server(server_id, &handler.game_server_manager)
+                    .await;
+            });
+
+            return Ok(());
+        }
+
+        #[cfg(feature = "discord")]
+        let (string_id, region) = (data.string_id.clone(), data.region.clone());
+        let gs_string_id = data.string_id.clone();
+
+        let server_id = self.game_server_manager.add_server(client, data);
         self.notify_servers_changed().await;
 
+        // create the default rooms in a separate task, as the server has to acknowledge them
+        let server = self.server();
+        tokio::spawn(async move {
+            let handler = server.handler();
+            handler
+                .module::<RoomModule>()
+                .host_default_rooms(server_id, &gs_string_id, &handler.game_server_manager)
+                .await;
+        });
+
+        // log on discord
+        #[cfg(feature = "discord")]
+        {
+            use crate::discord::{DiscordMessage, DiscordModule};
+
+            if let Some(discord) = self.opt_module::<DiscordModule>() {
+                discord.send_server_alert(
+                    &region,
+                    DiscordMessage::new()
+                        .content(format!("✅ Game server '{string_id}' ({region}) connected")),
+                );
+            }
+        }
+
         Ok(())
     }
 
+    /// Disconnects game servers that have not sent anything within the configured timeout
+    async fn check_game_servers(&self) {
+        // the timeout can be reloaded, 0 means the check was turned off
+        let timeout = Duration::from_secs(self.config().core().gs_idle_timeout_secs);
+        if timeout.is_zero() {
+            return;
+        }
+
+        for client in self.game_server_manager.unresponsive_servers(timeout) {
+            warn!(
+                "[{}] Game server has not responded in {:?}, disconnecting it",
+                client.address,
+                client.last_seen().elapsed()
+            );
+
+            // prevent the disconnect callback from handling this server a second time
+            client.set_authorized(false);
+            client.disconnect("timed out");
+            self.handle_game_server_disconnect(client).await;
+        }
+    }
+
     pub async fn handle_game_server_disconnect(&self, client: Arc<ClientState<GameServerHandler>>) {
-        let Some(srv) = self.game_server_manager.remove_server(&client) else {
+        let grace = Duration::from_secs(self.config().core().gs_reconnect_grace_secs);
+
+        let srv = if grace.is_zero() {
+            self.game_server_manager.remove_server(&client)
+        } else {
+            self.game_server_manager.detach_server(&client)
+        };
+
+        let Some(srv) = srv else {
             error!(
                 "[{} @ {}] unknown game server disconnected!",
                 client.connection_id, client.address
@@ -449,6 +907,35 @@ impl ConnectionHandler {
             srv.uptime()
         );
 
+        if grace.is_zero() {
+            self.finish_game_server_removal(srv).await;
+            return;
+        }
+
+        // give the server some time to reconnect before closing its rooms
+        let Some(since) = srv.disconnected_at() else {
+            return;
+        };
+
+        let server = self.server();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+
+            let handler = server.handler();
+            if let Some(srv) =
+                handler.game_server_manager.remove_detached(&srv.data.string_id, since)
+            {
+                info!(
+                    "Game server '{}' did not reconnect within {grace:?}, removing it",
+                    srv.data.string_id
+                );
+                handler.finish_game_server_removal(srv).await;
+            }
+        });
+    }
+
+    async fn finish_game_server_removal(&self, srv: StoredGameServer) {
         // close all rooms that are hosted on this server
         let module = self.module::<RoomModule>();
         module.close_all_rooms_on_server(srv.data.id, &self.game_server_manager).await;
@@ -462,11 +949,15 @@ impl ConnectionHandler {
             use crate::discord::{DiscordMessage, DiscordModule};
 
             if let Some(discord) = self.opt_module::<DiscordModule>() {
-                discord.send_server_alert(DiscordMessage::new().content(format!(
-                    "⚠️ Game server '{}' disconnected, was connected for {:?}",
-                    srv.data.string_id,
-                    srv.uptime()
-                )));
+                discord.send_server_alert(
+                    &srv.data.region,
+                    DiscordMessage::new().content(format!(
+                        "⚠️ Game server '{}' ({}) disconnected, was connected for {:?}",
+                        srv.data.string_id,
+                        srv.data.region,
+                        srv.uptime()
+                    )),
+                );
             }
         }
     }
@@ -502,6 +993,15 @@ impl ConnectionHandler {
         }
     }
 
+    /// Sends a notice to all connected clients that the server is about to shut down, so they
+    /// know why they got disconnected. Should be called right before `shutdown()`.
+    pub fn broadcast_shutdown(&self, reason: &str) {
+        match self.send_notice_all(None, reason, false, false) {
+            Ok(count) => info!("Notified {count} clients about server shutdown"),
+            Err(err) => error!("Failed to notify clients about server shutdown: {err}"),
+        }
+    }
+
     #[inline]
     pub async fn handle_game_server_room_created(&self, room_id: u32) {
         self.game_server_manager.ack_room_created(room_id).await;
@@ -589,6 +1089,23 @@ impl ConnectionHandler {
     async fn has_bad_word(&self, _string: &str) -> Option<String> {
         None
     }
+
+    /// Reloads the word filter from disk, returns the new word count or `None` if the filter is disabled.
+    /// If loading fails, the old filter stays active and the error is returned.
+    #[cfg(feature = "word-filter")]
+    pub async fn reload_word_filter(&self) -> Option<std::io::Result<usize>> {
+        use crate::word_filter::WordFilterModule;
+
+        match self.opt_module::<WordFilterModule>() {
+            Some(module) => Some(module.reload_from_file().await),
+            None => None,
+        }
+    }
+
+    #[cfg(not(feature = "word-filter"))]
+    pub async fn reload_word_filter(&self) -> Option<std::io::Result<usize>> {
+        None
+    }
 }
 
 fn format_systime(s: SystemTime) -> String {
@@ -651,12 +1168,20 @@ pub struct ServerHealth {
     pub argon_state: &'static str,
     /// How long we've been connected to the Argon server for in seconds, or null
     pub argon_connected_for: Option<f64>,
+    /// Clickhouse analytics state: disabled / down / up
+    pub analytics_state: &'static str,
+    /// How many analytics events have been dropped since startup
+    pub analytics_dropped_events: u64,
     /// How many clients in total are connected to the server
     pub clients: usize,
     /// How many rooms are currently active
     pub rooms: usize,
     /// How many levels are currently active across all rooms
     pub levels: usize,
+    /// How many logins have failed since startup, per reason
+    pub login_failures: BTreeMap<String, u64>,
+    /// How many messages failed to encode since startup, per call site
+    pub encode_failures: BTreeMap<String, u64>,
 
     /// Statuses of connected game servers
     pub game_servers: Vec<GameServerHealth>,
@@ -669,4 +1194,6 @@ pub struct GameServerHealth {
     pub uptime: f64,
     /// Server load, typically from 0 to 1 but can exceed 100%
     pub load: f32,
+    /// How many rooms are currently hosted on the server
+    pub rooms: u32,
 }
@@ -1,4 +1,4 @@
-use std::{num::NonZeroI64, sync::Arc};
+use std::{num::NonZeroI64, sync::Arc, time::Duration};
 
 use rand::seq::IteratorRandom;
 use server_shared::qunet::buffers::ByteWriter;
@@ -9,7 +9,9 @@ use crate::{
     users::UsersModule,
 };
 
-use super::{ConnectionHandler, util::*};
+use super::{ConnectionHandler, DisconnectReason, util::*};
+
+const TEAMS_UPDATE_DEBOUNCE: Duration = Duration::from_millis(100);
 
 impl ConnectionHandler {
     pub async fn handle_create_room(
@@ -31,6 +33,11 @@ impl ConnectionHandler {
             return self.send_room_banned(client, &p.reason, p.expires_at);
         }
 
+        if let Some(p) = client.active_mute.lock().as_ref() {
+            // muted users could otherwise get around the mute through room names
+            return self.send_muted(client, &p.reason, p.expires_at);
+        }
+
         let users = self.module::<UsersModule>();
         let rooms = self.module::<RoomModule>();
         let server_id = settings.server_id;
@@ -41,6 +48,14 @@ impl ConnectionHandler {
                 .send_room_create_failed(client, data::RoomCreateFailedReason::InvalidServer);
         }
 
+        // don't overload a server that is already hosting as many rooms as it can
+        if self.game_server_manager.is_server_full(server_id, self.config().core().gs_max_rooms) {
+            // there is no failure reason for a full server, and `InvalidServer` would tell the
+            // client the server doesn't exist, so only explain it with a warning
+            return self
+                .send_warn(client, "This server is full, try creating the room on another server");
+        }
+
         let default_name;
         // if the user is not allowed to name rooms, override the name with a default one
         if users.disallow_room_names() && client.role().as_ref().is_none_or(|r| !r.can_name_rooms) {
@@ -50,7 +65,9 @@ impl ConnectionHandler {
 
         // check if the name is a-ok
 
-        if let Some(word) = self.has_bad_word(name).await {
+        let trusted = users.is_trusted(client.account_id());
+
+        if !trusted && let Some(word) = self.has_bad_word(name).await {
             warn!(
                 "({}) disallowing room name '{}' due to banned word: '{}'",
                 client.account_id(),
@@ -61,6 +78,25 @@ impl ConnectionHandler {
                 .send_room_create_failed(client, data::RoomCreateFailedReason::InappropriateName);
         }
 
+        if !trusted && let Some(pattern) = rooms.blocked_name_pattern(name) {
+            warn!(
+                "({}) disallowing room name '{}' due to blocklisted pattern: '{}'",
+                client.account_id(),
+                name,
+                pattern
+            );
+            return self
+                .send_room_create_failed(client, data::RoomCreateFailedReason::InappropriateName);
+        }
+
+        let prefixed_name = rooms.apply_name_prefix(name);
+        let name = prefixed_name.as_str();
+
+        if !client.can_moderate() && !client.can_create_room(rooms.room_create_cooldown()) {
+            // like with a full server, none of the failure reasons fit, so only send a warning
+            return self.send_warn(client, "You are creating rooms too quickly, please wait a bit");
+        }
+
         let new_room = match rooms
             .create_room_and_join(name, passcode, settings, client, &self.game_server_manager)
             .await
@@ -71,6 +107,12 @@ impl ConnectionHandler {
                 return self
                     .send_room_create_failed(client, data::RoomCreateFailedReason::InvalidName);
             }
+
+            Err(e @ RoomCreationError::NameTaken) => {
+                self.send_warn(client, e.to_string())?;
+                return self
+                    .send_room_create_failed(client, data::RoomCreateFailedReason::InvalidName);
+            }
         };
 
         // notify the game server about the new room being created and wait for the response
@@ -80,6 +122,7 @@ impl ConnectionHandler {
             .await
         {
             Ok(()) => {
+                client.mark_room_created();
                 self.send_room_data(client, &new_room).await?;
             }
 
@@ -241,7 +284,7 @@ impl ConnectionHandler {
     }
 
     async fn send_room_data(&self, client: &ClientStateHandle, room: &Room) -> HandlerResult<()> {
-        self.send_room_players_filtered(client, room, true, false, |_| true).await
+        self.send_room_players_filtered(client, room, true, |_| true).await
     }
 
     async fn send_room_players_filtered(
@@ -249,17 +292,15 @@ impl ConnectionHandler {
         client: &ClientStateHandle,
         room: &Room,
         full_room_check: bool,
-        minimal: bool,
         filter: impl Fn(&ClientStateHandle) -> bool,
     ) -> HandlerResult<()> {
         let players = self.pick_players_to_send(client, room, filter).await;
         let total_player_count = room.player_count();
 
-        let players_cap = if minimal && !full_room_check {
-            players.iter().map(|_| 64).sum::<usize>()
-        } else {
-            players.iter().map(bytes_for_room_player).sum::<usize>()
-        };
+        let players_cap = players
+            .iter()
+            .map(|p| bytes_for_room_player(p.username()) + bytes_for_special_data(p))
+            .sum::<usize>();
 
         let is_mod = client.can_moderate();
 
@@ -295,7 +336,7 @@ impl ConnectionHandler {
                     });
                 }
             })?
-        } else if !minimal {
+        } else {
             let cap = 56 + players_cap;
 
             data::encode_message_heap!(self, cap, msg => {
@@ -308,19 +349,6 @@ impl ConnectionHandler {
                     Self::encode_room_player(is_mod, player, player_ser.reborrow());
                 }
             })?
-        } else {
-            let cap = 48 + players_cap;
-
-            data::encode_message_heap!(self, cap, msg => {
-                let mut room_players = msg.reborrow().init_global_players();
-
-                let mut players_ser = room_players.reborrow().init_players(players.len() as u32);
-
-                for (i, player) in players.iter().enumerate() {
-                    let mut player_ser = players_ser.reborrow().get(i as u32);
-                    Self::encode_minimal_room_player(player, player_ser.reborrow());
-                }
-            })?
         };
 
         client.send_data_bufkind(buf);
@@ -334,10 +362,8 @@ impl ConnectionHandler {
         room: &Room,
         filter: impl Fn(&ClientStateHandle) -> bool,
     ) -> Vec<ClientStateHandle> {
-        const PLAYER_CAP: usize = 100;
-
         let player_count = if room.is_global() {
-            room.player_count().min(PLAYER_CAP)
+            room.player_count().min(self.module::<RoomModule>().global_room_player_cap())
         } else {
             room.player_count()
         };
@@ -416,7 +442,7 @@ impl ConnectionHandler {
         must_auth(client)?;
 
         if let Some(room) = client.get_room() {
-            self.send_room_players(client, &room, name_filter, false).await?;
+            self.send_room_players(client, &room, name_filter).await?;
         }
 
         Ok(())
@@ -430,7 +456,30 @@ impl ConnectionHandler {
         must_auth(client)?;
 
         let room = self.module::<RoomModule>().global_room();
-        self.send_room_players(client, &room, name_filter, true).await
+
+        let players = self
+            .pick_players_to_send(client, &room, |p| {
+                name_filter.is_empty() || username_match(p.username(), name_filter)
+            })
+            .await;
+
+        let cap =
+            48 + players.iter().map(|p| bytes_for_minimal_room_player(p.username())).sum::<usize>();
+
+        let buf = data::encode_message_heap!(self, cap, msg => {
+            let mut global_players = msg.reborrow().init_global_players();
+
+            let mut players_ser = global_players.reborrow().init_players(players.len() as u32);
+
+            for (i, player) in players.iter().enumerate() {
+                let mut player_ser = players_ser.reborrow().get(i as u32);
+                Self::encode_minimal_room_player(player, player_ser.reborrow());
+            }
+        })?;
+
+        client.send_data_bufkind(buf);
+
+        Ok(())
     }
 
     async fn send_room_players(
@@ -438,12 +487,11 @@ impl ConnectionHandler {
         client: &ClientStateHandle,
         room: &Room,
         name_filter: &str,
-        minimal: bool,
     ) -> HandlerResult<()> {
         if name_filter.is_empty() {
-            self.send_room_players_filtered(client, room, false, minimal, |_| true).await?;
+            self.send_room_players_filtered(client, room, false, |_| true).await?;
         } else {
-            self.send_room_players_filtered(client, room, false, minimal, |p| {
+            self.send_room_players_filtered(client, room, false, |p| {
                 username_match(p.username(), name_filter)
             })
             .await?;
@@ -702,9 +750,7 @@ impl ConnectionHandler {
         debug!("{} is creating invite for {} (room {})", client.account_id(), player, room.id);
         if !client.try_invite() {
             warn!("{} has exceeded the invite limit, disconnecting", client.account_id());
-            client.disconnect(
-                "Too many invites sent in a short period, please stop spamming invites",
-            );
+            self.disconnect_client(client, DisconnectReason::InviteSpam);
 
             return Ok(());
         }
@@ -765,7 +811,30 @@ impl ConnectionHandler {
         Ok(())
     }
 
-    fn notify_teams_updated(&self, room: &Room) -> HandlerResult<()> {
+    /// Schedules a broadcast of the room's teams. Multiple changes within a short time window
+    /// are coalesced into a single message, so that an owner spamming team changes can't flood the room.
+    fn notify_teams_updated(&self, room: &Arc<Room>) -> HandlerResult<()> {
+        if !room.mark_teams_dirty() {
+            // broadcast already scheduled, it will include this change
+            return Ok(());
+        }
+
+        let server = self.server();
+        let room = room.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(TEAMS_UPDATE_DEBOUNCE).await;
+            room.clear_teams_dirty();
+
+            if let Err(e) = server.handler().send_teams_updated(&room) {
+                warn!("failed to send teams update to room {}: {e}", room.id);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn send_teams_updated(&self, room: &Room) -> HandlerResult<()> {
         let buf = room.with_teams(|team_count, teams| {
             data::encode_message_dyn!(self, msg => {
                 let mut teams_ser = msg.reborrow().init_teams_updated().init_teams(team_count as u32);
@@ -835,15 +904,41 @@ impl ConnectionHandler {
     }
 }
 
+/// Case-insensitive (ASCII only) substring match. An empty filter matches everything, a filter
+/// longer than the name never matches. Works on bytes, so non-ASCII names can't cause a panic.
 fn username_match(username: &str, filter: &str) -> bool {
+    // `windows` panics with a size of 0
+    if filter.is_empty() {
+        return true;
+    }
+
+    if filter.len() > username.len() {
+        return false;
+    }
+
     username
         .as_bytes()
         .windows(filter.len())
         .any(|window| window.eq_ignore_ascii_case(filter.as_bytes()))
 }
 
-fn bytes_for_room_player(client: &ClientStateHandle) -> usize {
-    80 + if let Some(role) = &*client.role()
+/// Size of a capnp text field: it is NUL-terminated and padded to a full word
+fn bytes_for_text(s: &str) -> usize {
+    (s.len() + 1).next_multiple_of(8)
+}
+
+fn bytes_for_minimal_room_player(username: &str) -> usize {
+    // struct, pointers and account data without the username
+    48 + bytes_for_text(username)
+}
+
+fn bytes_for_room_player(username: &str) -> usize {
+    // same as the minimal player, plus the session and team
+    64 + bytes_for_text(username)
+}
+
+fn bytes_for_special_data(client: &ClientStateHandle) -> usize {
+    if let Some(role) = &*client.role()
         && role.is_special()
     {
         40 + role.roles.len() + role.name_color.as_ref().map(|x| x.encoded_len()).unwrap_or(0)
@@ -884,3 +979,87 @@ fn get_custom_room_as_owner(c: &ClientStateHandle) -> HandlerResult<Arc<Room>> {
         _ => Err(HandlerError::NotInCustomRoom),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USERNAME: &str = "sixteen_chars_ab";
+
+    /// Size of the message without the root pointer, which is what a list element takes up
+    fn encoded_size(message: &capnp::message::Builder<capnp::message::HeapAllocator>) -> usize {
+        message.get_segments_for_output().iter().map(|s| s.len()).sum::<usize>() - 8
+    }
+
+    fn fill_account_data(mut builder: data::player_account_data::Builder<'_>) {
+        builder.set_account_id(i32::MAX);
+        builder.set_user_id(i32::MAX);
+        builder.set_username(USERNAME);
+    }
+
+    #[test]
+    fn username_match_empty_filter() {
+        assert!(username_match("Player", ""));
+        assert!(username_match("", ""));
+    }
+
+    #[test]
+    fn username_match_case_insensitive() {
+        assert!(username_match("SomePlayer", "play"));
+        assert!(username_match("SomePlayer", "SOMEPLAYER"));
+        assert!(!username_match("SomePlayer", "other"));
+    }
+
+    #[test]
+    fn username_match_filter_longer_than_name() {
+        assert!(!username_match("abc", "abcd"));
+        assert!(!username_match("", "a"));
+    }
+
+    #[test]
+    fn username_match_non_ascii() {
+        // must not panic on multibyte characters, whether in the name, the filter or both
+        assert!(username_match("Jösé", "ösé"));
+        assert!(username_match("プレイヤー", "イヤ"));
+        assert!(!username_match("Jose", "ö"));
+        assert!(!username_match("ö", "öö"));
+
+        // only ASCII is case folded
+        assert!(!username_match("Ö", "ö"));
+    }
+
+    #[test]
+    fn text_size() {
+        assert_eq!(bytes_for_text(""), 8);
+        assert_eq!(bytes_for_text("1234567"), 8);
+        assert_eq!(bytes_for_text(USERNAME), 24);
+    }
+
+    #[test]
+    fn minimal_room_player_size() {
+        let mut message = capnp::message::Builder::new_default();
+        let mut builder = message.init_root::<data::minimal_room_player::Builder<'_>>();
+        builder.set_cube(i16::MAX);
+        builder.set_color1(u16::MAX);
+        builder.set_color2(u16::MAX);
+        builder.set_glow_color(u16::MAX);
+        fill_account_data(builder.init_account_data());
+
+        assert!(bytes_for_minimal_room_player(USERNAME) >= encoded_size(&message));
+    }
+
+    #[test]
+    fn room_player_size() {
+        let mut message = capnp::message::Builder::new_default();
+        let mut builder = message.init_root::<data::room_player::Builder<'_>>();
+        builder.set_cube(i16::MAX);
+        builder.set_color1(u16::MAX);
+        builder.set_color2(u16::MAX);
+        builder.set_glow_color(u16::MAX);
+        builder.set_session(u64::MAX);
+        builder.set_team_id(u16::MAX);
+        fill_account_data(builder.init_account_data());
+
+        assert!(bytes_for_room_player(USERNAME) >= encoded_size(&message));
+    }
+}
@@ -1,6 +1,6 @@
 use server_shared::SessionId;
 
-use crate::users::UsersModule;
+use crate::{rooms::RoomModule, users::UsersModule};
 
 use super::{ConnectionHandler, util::*};
 
@@ -25,6 +25,23 @@ impl ConnectionHandler {
             return self.on_join_failed(client, data::JoinSessionFailedReason::InvalidServer);
         }
 
+        // levels in a custom room must be played on the game server hosting that room
+        if self.module::<RoomModule>().enforce_room_server()
+            && let Some(room) = client.get_room()
+            && !room.allows_server(session_id.server_id())
+        {
+            debug!(
+                "[{} @ {}] rejecting session on server {}, room {} is on server {}",
+                client.account_id(),
+                client.address,
+                session_id.server_id(),
+                room.id,
+                room.server_id()
+            );
+
+            return self.on_join_failed(client, data::JoinSessionFailedReason::InvalidServer);
+        }
+
         let prev_id = client.set_session_id(session_id.as_u64());
         self.handle_session_change(client, SessionId::from(prev_id), session_id, Some(author_id))
             .await?;
@@ -75,6 +92,7 @@ impl ConnectionHandler {
 
         if !prev_session.is_zero() {
             self.decrement_level_players(prev_session);
+            self.remove_session_client(prev_session, client.account_id());
         }
 
         if !new_session.is_zero() {
@@ -82,6 +100,7 @@ impl ConnectionHandler {
                 || new_author.is_some_and(|x| users.is_author_blacklisted(x));
 
             self.increment_level_players(new_session, is_blacklisted);
+            self.add_session_client(new_session, client.account_id());
 
             let users = self.module::<UsersModule>();
             let data = users.gather_user_data(client);
@@ -122,3 +141,69 @@ impl ConnectionHandler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the session id is packed as server id (8 bits) | room id (24 bits) | level id (32 bits)
+    fn pack(server_id: u8, room_id: u32, level_id: i32) -> u64 {
+        ((server_id as u64) << 56) | (((room_id & 0xff_ffff) as u64) << 32) | level_id as u32 as u64
+    }
+
+    #[test]
+    fn components_of_a_known_value() {
+        let id = SessionId::from(0x0102_0304_0506_0708);
+
+        assert_eq!(id.server_id(), 0x01);
+        assert_eq!(id.room_id(), 0x02_0304);
+        assert_eq!(id.level_id(), 0x0506_0708);
+    }
+
+    #[test]
+    fn components_at_boundaries() {
+        let cases = [
+            (0, 0, 0),
+            (1, 0, 0),
+            (u8::MAX, 0, 0),
+            (0, 1, 0),
+            (0, 0xff_ffff, 0),
+            (0, 0, 1),
+            (0, 0, i32::MAX),
+            (u8::MAX, 0xff_ffff, i32::MAX),
+            (7, 999_999, 128_053_751),
+        ];
+
+        for (server_id, room_id, level_id) in cases {
+            let raw = pack(server_id, room_id, level_id);
+            let id = SessionId::from(raw);
+
+            assert_eq!(id.as_u64(), raw);
+            assert_eq!(id.server_id(), server_id, "server id of {raw:#x}");
+            assert_eq!(id.room_id(), room_id, "room id of {raw:#x}");
+            assert_eq!(id.level_id(), level_id, "level id of {raw:#x}");
+        }
+    }
+
+    #[test]
+    fn components_do_not_overlap() {
+        assert_eq!(pack(u8::MAX, 0, 0), 0xff00_0000_0000_0000);
+        assert_eq!(pack(0, 0xff_ffff, 0), 0x00ff_ffff_0000_0000);
+        assert_eq!(pack(0, 0, i32::MAX), 0x0000_0000_7fff_ffff);
+
+        let max_level = SessionId::from(pack(0, 0, i32::MAX));
+        assert_eq!(max_level.server_id(), 0);
+        assert_eq!(max_level.room_id(), 0);
+
+        let max_server = SessionId::from(pack(u8::MAX, 0, 0));
+        assert_eq!(max_server.room_id(), 0);
+        assert_eq!(max_server.level_id(), 0);
+    }
+
+    #[test]
+    fn zero_is_zero() {
+        assert!(SessionId::from(0).is_zero());
+        assert!(SessionId(0).is_zero());
+        assert!(!SessionId::from(pack(0, 0, 1)).is_zero());
+    }
+}
@@ -41,3 +41,18 @@ where
         }
     }
 }
+
+/// Compares two strings without exiting early on the first mismatch, for checking secrets
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut result = 0u8;
+
+    for (a_byte, b_byte) in a.bytes().zip(b.bytes()) {
+        result |= a_byte ^ b_byte;
+    }
+
+    result == 0
+}
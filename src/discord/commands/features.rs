@@ -9,6 +9,8 @@ use crate::{
         "send",
         "queue",
         "unsend",
+        "remove_queued",
+        "remove_featured",
         "update_spreadsheet",
         "set_duration",
         "set_priority",
@@ -28,7 +30,7 @@ pub async fn update_spreadsheet(ctx: Context<'_>) -> Result<(), BotError> {
     let server = ctx.data().server()?;
     let features = server.handler().module::<FeaturesModule>();
 
-    features.update_spreadsheet(true, true, true).await;
+    features.update_spreadsheet_now(true, true, true).await;
 
     ctx.reply("✅ Requested spreadsheet update. It may take a few minutes to update.").await?;
 
@@ -155,6 +157,48 @@ pub async fn unsend(ctx: Context<'_>, level_id: i32) -> Result<(), BotError> {
     Ok(())
 }
 
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Remove a level from the feature queue
+pub async fn remove_queued(ctx: Context<'_>, level_id: i32) -> Result<(), BotError> {
+    check_admin(ctx).await?;
+
+    let server = ctx.data().server()?;
+    let features = server.handler().module::<FeaturesModule>();
+
+    match features.remove_queued_level(level_id).await {
+        Ok(()) => {
+            ctx.reply("✅ Successfully removed level from the queue.").await?;
+        }
+
+        Err(e) => {
+            ctx.reply(format!(":x: Failed to remove level from the queue: {e}")).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Un-feature a level, if it is currently featured, the previous level is featured again
+pub async fn remove_featured(ctx: Context<'_>, level_id: i32) -> Result<(), BotError> {
+    check_admin(ctx).await?;
+
+    let server = ctx.data().server()?;
+    let features = server.handler().module::<FeaturesModule>();
+
+    match features.remove_featured_level(level_id).await {
+        Ok(()) => {
+            ctx.reply("✅ Successfully removed level from the featured list.").await?;
+        }
+
+        Err(e) => {
+            ctx.reply(format!(":x: Failed to remove featured level: {e}")).await?;
+        }
+    }
+
+    Ok(())
+}
+
 #[poise::command(slash_command, ephemeral = true, guild_only = true)]
 /// Set the feature duration for a level
 pub async fn set_duration(
@@ -174,6 +218,18 @@ pub async fn set_duration(
     };
 
     let features = server.handler().module::<FeaturesModule>();
+
+    let (min, max) = features.feature_duration_bounds();
+    if !dur.is_zero() && (dur < min || dur > max) {
+        ctx.reply(format!(
+            ":x: Feature duration must be between {} and {}!",
+            format_duration(min),
+            format_duration(max)
+        ))
+        .await?;
+        return Ok(());
+    }
+
     if let Err(e) = features.set_feature_duration(level_id, dur).await {
         ctx.reply(format!(":x: Failed to set feature duration: {e}")).await?;
         return Ok(());
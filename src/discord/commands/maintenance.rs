@@ -106,13 +106,17 @@ pub async fn shutdown_server(ctx: Context<'_>, message: Option<String>) -> Resul
             let server = state.server()?;
 
             // send a message to all users on the server, if requested
-            if let Some(msg) = message {
-                let _ = server.handler().send_notice_all(None, &msg, false, false);
-
-                // wait a bit, it might take some time for the message to send successfully
-                tokio::time::sleep(Duration::from_millis(100)).await;
+            if let Some(msg) = &message {
+                let _ = server.handler().send_notice_all(None, msg, false, false);
             }
 
+            server
+                .handler()
+                .broadcast_shutdown(message.as_deref().unwrap_or("The server is shutting down"));
+
+            // wait a bit, it might take some time for the messages to send successfully
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
             ctx.say("Goodbye!").await?;
 
             server.shutdown();
@@ -166,6 +170,125 @@ pub async fn status(ctx: Context<'_>) -> Result<(), BotError> {
     Ok(())
 }
 
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Show the permissions that a combination of roles results in
+pub async fn compute_role(
+    ctx: Context<'_>,
+    #[description = "Role IDs, separated by commas"] roles: String,
+) -> Result<(), BotError> {
+    let server = ctx.data().server()?;
+    let users = server.handler().module::<UsersModule>();
+
+    check_linked_and(ctx, |u| users.is_super_admin(u.account_id)).await?;
+
+    let role_ids: Vec<&str> = roles.split(',').map(str::trim).filter(|x| !x.is_empty()).collect();
+    let unknown: Vec<&str> =
+        role_ids.iter().copied().filter(|id| users.get_role_by_str_id(id).is_none()).collect();
+
+    let computed = users.compute_from_roles(0, role_ids.iter().copied());
+
+    let perms = [
+        ("Staff", computed.is_staff),
+        ("Kick", computed.can_kick),
+        ("Mute", computed.can_mute),
+        ("Ban", computed.can_ban),
+        ("Set password", computed.can_set_password),
+        ("Notice everyone", computed.can_notice_everyone),
+        ("Edit roles", computed.can_edit_roles),
+        ("Send features", computed.can_send_features),
+        ("Rate features", computed.can_rate_features),
+        ("Name rooms", computed.can_name_rooms),
+    ];
+
+    let perms_str = perms
+        .iter()
+        .map(|(name, on)| format!("{} {name}", if *on { "✅" } else { "❌" }))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut role_names = users.make_role_string(&computed.roles);
+    if role_names.is_empty() {
+        role_names = "none".to_owned();
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("Computed role")
+        .color(hex_color_to_decimal("#00bfff"))
+        .field("Roles", role_names, false)
+        .field("Priority", computed.priority.to_string(), true)
+        .field("Name color", computed.name_color.is_some().to_string(), true)
+        .field("Permissions", perms_str, false);
+
+    if !unknown.is_empty() {
+        embed = embed.field("Unknown roles (ignored)", unknown.join(", "), false);
+    }
+
+    ctx.send(ctx.reply_builder(CreateReply::default().embed(embed))).await?;
+
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Show which game server a room is hosted on
+pub async fn room_server(
+    ctx: Context<'_>,
+    #[description = "Room ID"] room_id: u32,
+) -> Result<(), BotError> {
+    check_moderator(ctx).await?;
+
+    let server = ctx.data().server()?;
+
+    let Some(server_id) = server.handler().module::<RoomModule>().get_room_server(room_id) else {
+        ctx.reply(format!(":x: Room {room_id} does not exist.")).await?;
+        return Ok(());
+    };
+
+    let servers = server.handler().get_game_servers();
+    match servers.iter().find(|s| s.data.id == server_id) {
+        Some(gs) => {
+            ctx.reply(format!(
+                "Room {room_id} is hosted on {} ({} / nID {}, region {})",
+                gs.data.name, gs.data.string_id, gs.data.id, gs.data.region
+            ))
+            .await?;
+        }
+
+        None => {
+            ctx.reply(format!(
+                ":warning: Room {room_id} is on game server nID {server_id}, which is not connected."
+            ))
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Pin a room to the top of the room list, or unpin it
+pub async fn pin_room(
+    ctx: Context<'_>,
+    #[description = "Room ID"] room_id: u32,
+    pinned: bool,
+) -> Result<(), BotError> {
+    check_admin(ctx).await?;
+
+    let server = ctx.data().server()?;
+
+    if server.handler().module::<RoomModule>().set_room_pinned(room_id, pinned) {
+        info!(
+            "{} {} room {room_id}",
+            ctx.author().name,
+            if pinned { "pinned" } else { "unpinned" }
+        );
+        ctx.reply("✅ Success").await?;
+    } else {
+        ctx.reply(format!(":x: Room {room_id} does not exist.")).await?;
+    }
+
+    Ok(())
+}
+
 #[poise::command(slash_command, ephemeral = true, guild_only = true)]
 /// Reloads the server configuration and applies all possible changes at runtime
 pub async fn reload_config(ctx: Context<'_>) -> Result<(), BotError> {
@@ -181,6 +304,29 @@ pub async fn reload_config(ctx: Context<'_>) -> Result<(), BotError> {
     Ok(())
 }
 
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Reloads the word filter from disk
+pub async fn reload_word_filter(ctx: Context<'_>) -> Result<(), BotError> {
+    let state = ctx.data();
+    let server = state.server()?;
+    let users = server.handler().module::<UsersModule>();
+
+    check_linked_and(ctx, |u| users.is_super_admin(u.account_id)).await?;
+
+    match server.handler().reload_word_filter().await {
+        Some(Ok(count)) => {
+            ctx.reply(format!("✅ Word filter reloaded, {count} words loaded.")).await?
+        }
+        Some(Err(e)) => {
+            ctx.reply(format!(":x: Failed to reload the word filter, keeping the old one: {e}"))
+                .await?
+        }
+        None => ctx.reply(":x: Word filter is not enabled on this server.").await?,
+    };
+
+    Ok(())
+}
+
 #[poise::command(slash_command, ephemeral = true, guild_only = true)]
 /// Dump and show connection stats
 pub async fn conn_stats(ctx: Context<'_>) -> Result<(), BotError> {
@@ -382,6 +528,12 @@ fn collect_perf_stats(server: &ServerHandle<ConnectionHandler>) -> CreateEmbed {
 
     embed = embed.field("Buffer pool", format!("{}", ByteCount(bpool.total_heap_usage)), true);
 
+    #[cfg(feature = "analytics")]
+    if let Some(analytics) = server.handler().opt_module::<crate::analytics::AnalyticsModule>() {
+        embed =
+            embed.field("Dropped analytics events", analytics.dropped_events().to_string(), true);
+    }
+
     embed
 }
 
@@ -20,9 +20,14 @@ pub fn all() -> Vec<poise::Command<Arc<BotState>, BotError>> {
         link::linkinfo(),
         moderation::punish(),
         moderation::unpunish(),
+        moderation::mass_unpunish(),
         moderation::audit_log(),
         moderation::check_actions(),
         moderation::check_alts(),
+        moderation::link_accounts(),
+        moderation::unlink_account(),
+        moderation::notes(),
+        moderation::username_history(),
         moderation::kick(),
         moderation::kick_all(),
         #[cfg(feature = "featured-levels")]
@@ -32,7 +37,11 @@ pub fn all() -> Vec<poise::Command<Arc<BotState>, BotError>> {
         maintenance::shutdown_server(),
         maintenance::disallow_joins(),
         maintenance::status(),
+        maintenance::room_server(),
+        maintenance::pin_room(),
+        maintenance::compute_role(),
         maintenance::reload_config(),
+        maintenance::reload_word_filter(),
         maintenance::conn_stats(),
         maintenance::player_count(),
         misc::say(),
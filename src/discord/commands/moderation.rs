@@ -13,7 +13,7 @@ use poise::{
     CreateReply,
     serenity_prelude::{self as serenity, AutocompleteChoice, CreateEmbed, EmbedField},
 };
-use tracing::info;
+use tracing::{info, warn};
 
 async fn punish_autocomplete(
     _ctx: Context<'_>,
@@ -117,6 +117,108 @@ pub async fn unpunish(
     Ok(())
 }
 
+const MASS_UNPUNISH_PREVIEW_LIMIT: usize = 25;
+
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Removes all active punishments of a type issued by someone within a time window
+pub async fn mass_unpunish(
+    ctx: Context<'_>,
+    #[autocomplete = "punish_autocomplete"]
+    #[description = "Punishment type"]
+    punishment_type: String,
+    #[autocomplete = "db_user_autocomplete"]
+    #[description = "GD username or account ID of the user who issued the punishments"]
+    issuer: String,
+    #[description = "Start of the window, how long ago (i.e. \"2 hours\", \"1 day\")"]
+    since: String,
+    #[description = "End of the window, how long ago (defaults to now)"] until: Option<String>,
+    #[description = "Only list the affected accounts without unpunishing them (default true)"]
+    dry_run: Option<bool>,
+) -> Result<(), BotError> {
+    let server = ctx.data().server()?;
+    let users = server.handler().module::<UsersModule>();
+
+    let user = check_linked_and(ctx, |u| users.is_super_admin(u.account_id)).await?;
+
+    let pun_type = parse_punish_type(&punishment_type).unwrap_or(UserPunishmentType::Ban);
+
+    let Some(issuer) = users.query_user(&issuer).await? else {
+        ctx.reply(":x: Failed to find the issuer by the given name").await?;
+        return Ok(());
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+    let after = now - parse_duration_str(&since)?.as_secs() as i64;
+    let before = match until {
+        Some(s) => now - parse_duration_str(&s)?.as_secs() as i64,
+        None => now + 1,
+    };
+
+    if after >= before {
+        ctx.reply(":x: The start of the window must be before the end").await?;
+        return Ok(());
+    }
+
+    let targets = users.find_punished_by(issuer.account_id, pun_type, after, before).await?;
+
+    if targets.is_empty() {
+        ctx.reply(format!(
+            ":x: No accounts found with an active {punishment_type} issued by {issuer} in this window"
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    if dry_run.unwrap_or(true) {
+        let mut out = format!(
+            "{} accounts would be unpunished ({punishment_type} by {issuer}):\n",
+            targets.len()
+        );
+
+        for target in targets.iter().take(MASS_UNPUNISH_PREVIEW_LIMIT) {
+            let _ = writeln!(out, "- {target}");
+        }
+
+        if targets.len() > MASS_UNPUNISH_PREVIEW_LIMIT {
+            let _ = writeln!(out, "...and {} more", targets.len() - MASS_UNPUNISH_PREVIEW_LIMIT);
+        }
+
+        out.push_str("Run again with `dry_run: False` to apply.");
+        ctx.reply(out).await?;
+        return Ok(());
+    }
+
+    ctx.defer_ephemeral().await?;
+
+    let mut done = 0usize;
+    let mut failed = 0usize;
+
+    for target in &targets {
+        match server.handler().do_unpunish_user(user.account_id, target.account_id, pun_type).await
+        {
+            Ok(()) => {
+                info!(
+                    "{} mass-unpunished {target} ({punishment_type} issued by {})",
+                    user.account_id, issuer.account_id
+                );
+                done += 1;
+            }
+
+            Err(e) => {
+                warn!("Failed to mass-unpunish {target}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    ctx.reply(format!(
+        ":white_check_mark: Removed {done} punishments ({punishment_type} by {issuer}), {failed} failed"
+    ))
+    .await?;
+
+    Ok(())
+}
+
 #[allow(clippy::format_in_format_args)]
 async fn audit_log_embed(
     logs: Vec<AuditLogModel>,
@@ -322,14 +424,92 @@ pub async fn check_alts(
         }
     };
 
+    let linked = match users.query_user(&user).await? {
+        Some(u) => users.get_linked_accounts(u.account_id).await?,
+        None => Vec::new(),
+    };
+
     let mut out_str = format!("Found {} accounts:\n", alts.len());
 
     for id in alts {
         let acc = users.get_user(id).await?;
 
         let username = acc.as_ref().and_then(|u| u.username.as_deref()).unwrap_or("Unknown");
+        let suffix = if linked.contains(&id) { " (known linked)" } else { "" };
+
+        writeln!(out_str, "* {} ({}){}", username, id, suffix).unwrap();
+    }
+
+    ctx.reply(out_str).await?;
+
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Shows or changes the moderator note of a user
+pub async fn notes(
+    ctx: Context<'_>,
+    #[autocomplete = "db_user_autocomplete"]
+    #[description = "GD username or account ID of the target user"]
+    user: String,
+    #[max_length = 1024]
+    #[description = "New note, use \"-\" to clear the note. Leave empty to only show the current note"]
+    new_note: Option<String>,
+) -> Result<(), BotError> {
+    let issuer = check_moderator(ctx).await?;
+
+    let server = ctx.data().server()?;
+    let users = server.handler().module::<UsersModule>();
+
+    let Some(target) = users.query_user(&user).await? else {
+        ctx.reply(":x: Failed to find the user by the given name").await?;
+        return Ok(());
+    };
+
+    match new_note {
+        Some(note) => {
+            let note = if note.trim() == "-" { "" } else { note.trim() };
+            users.admin_set_note(issuer.account_id, target.account_id, note).await?;
 
-        writeln!(out_str, "* {} ({})", username, id).unwrap();
+            ctx.reply(format!(":white_check_mark: Updated the note for {target}")).await?;
+        }
+
+        None => {
+            let note = target.note.as_deref().unwrap_or("*No note set*");
+            ctx.reply(format!("**Note for {target}:**\n{note}")).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Shows the previous usernames of a user
+pub async fn username_history(
+    ctx: Context<'_>,
+    #[autocomplete = "db_user_autocomplete"]
+    #[description = "GD username or account ID of the target user"]
+    user: String,
+) -> Result<(), BotError> {
+    check_moderator(ctx).await?;
+
+    let server = ctx.data().server()?;
+    let users = server.handler().module::<UsersModule>();
+
+    let Some(target) = users.query_user(&user).await? else {
+        ctx.reply(":x: Failed to find the user by the given name").await?;
+        return Ok(());
+    };
+
+    let history = users.get_username_history(target.account_id).await?;
+    if history.is_empty() {
+        ctx.reply(format!("No previous usernames recorded for {target}")).await?;
+        return Ok(());
+    }
+
+    let mut out_str = format!("Previous usernames of {target}:\n");
+    for entry in history {
+        writeln!(out_str, "* {} (until <t:{}>)", entry.username, entry.changed_at).unwrap();
     }
 
     ctx.reply(out_str).await?;
@@ -337,6 +517,68 @@ pub async fn check_alts(
     Ok(())
 }
 
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Marks accounts as belonging to the same person, so they are not flagged as alts of each other
+pub async fn link_accounts(
+    ctx: Context<'_>,
+    #[description = "GD usernames or account IDs, separated by commas"] accounts: String,
+) -> Result<(), BotError> {
+    let server = ctx.data().server()?;
+    let users = server.handler().module::<UsersModule>();
+
+    let issuer = check_linked_and(ctx, |u| users.is_super_admin(u.account_id)).await?;
+
+    let mut ids = Vec::new();
+    for query in accounts.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        match users.query_user(query).await? {
+            Some(u) => ids.push(u.account_id),
+            None => {
+                ctx.reply(format!(":x: Failed to find the user '{query}'")).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    if ids.len() < 2 {
+        ctx.reply(":x: At least two accounts are required").await?;
+        return Ok(());
+    }
+
+    users.link_accounts(issuer.account_id, &ids).await?;
+
+    let linked = users.get_linked_accounts(ids[0]).await?;
+    ctx.reply(format!("✅ Accounts linked. All accounts in this group: {linked:?}")).await?;
+
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral = true, guild_only = true)]
+/// Removes an account from its group of linked accounts
+pub async fn unlink_account(
+    ctx: Context<'_>,
+    #[autocomplete = "db_user_autocomplete"]
+    #[description = "GD username or account ID of the target user"]
+    user: String,
+) -> Result<(), BotError> {
+    let server = ctx.data().server()?;
+    let users = server.handler().module::<UsersModule>();
+
+    let issuer = check_linked_and(ctx, |u| users.is_super_admin(u.account_id)).await?;
+
+    let Some(target) = users.query_user(&user).await? else {
+        ctx.reply(":x: Failed to find the user by the given name").await?;
+        return Ok(());
+    };
+
+    if users.unlink_account(issuer.account_id, target.account_id).await? {
+        ctx.reply(format!("✅ Unlinked {target}")).await?;
+    } else {
+        ctx.reply(format!(":x: {target} is not linked to any accounts")).await?;
+    }
+
+    Ok(())
+}
+
 #[poise::command(slash_command, ephemeral = true, guild_only = true)]
 pub async fn kick(
     ctx: Context<'_>,
@@ -136,6 +136,21 @@ pub fn parse_duration_str(s: &str) -> Result<Duration, ParseDurationError> {
     Ok(Duration::from_secs(number * modifier))
 }
 
+/// Formats a duration in a short human readable form, e.g. `2d 4h 30m`
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (days, hours, mins, secs) =
+        (secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60, secs % 60);
+
+    let parts: Vec<String> = [(days, "d"), (hours, "h"), (mins, "m"), (secs, "s")]
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{n}{unit}"))
+        .collect();
+
+    if parts.is_empty() { "0s".to_owned() } else { parts.join(" ") }
+}
+
 fn fuzzy_match(target: &str, candidate: &str) -> i64 {
     let matcher = SkimMatcherV2::default();
     matcher.fuzzy_match(target, candidate).unwrap_or(-1)
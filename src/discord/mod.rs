@@ -1,5 +1,5 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fmt::Write,
     sync::Arc,
     time::{Duration, Instant},
@@ -92,13 +92,25 @@ impl DiscordModule {
         self.send_message(config.alert_channel, msg)
     }
 
-    pub fn send_server_alert(&self, msg: DiscordMessage<'_>) {
+    /// Sends an alert to the channel configured for the given region,
+    /// falling back to the default alert channel if the region is not mapped
+    pub fn send_alert_for_region(&self, region: &str, msg: DiscordMessage<'_>) {
         let config = self.config.load();
-        if config.server_alert_channel == 0 {
-            return;
+        let channel = config.region_alert_channel(region).unwrap_or(config.alert_channel);
+
+        if channel != 0 {
+            self.send_message(channel, msg)
         }
+    }
+
+    /// Like `send_alert_for_region`, but falls back to the server alert channel
+    pub fn send_server_alert(&self, region: &str, msg: DiscordMessage<'_>) {
+        let config = self.config.load();
+        let channel = config.region_alert_channel(region).unwrap_or(config.server_alert_channel);
 
-        self.send_message(config.server_alert_channel, msg)
+        if channel != 0 {
+            self.send_message(channel, msg)
+        }
     }
 
     pub fn send_ticket_ping(&self, ticket_channel: u64, moderator_id: u64) {
@@ -209,6 +221,18 @@ impl DiscordModule {
         });
     }
 
+    pub fn send_ban_evasion_alert(&self, username: &str, account_id: i32, banned_id: i32) {
+        self.send_alert(DiscordMessage::new().content(format!(
+            "🚫 Blocked login from {username} ({account_id}), shares a uident with banned account {banned_id}"
+        )));
+    }
+
+    pub fn send_welcome_alert(&self, username: &str, account_id: i32) {
+        self.send_alert(DiscordMessage::new().content(format!(
+            "👋 New player {username} ({account_id}) logged in for the first time"
+        )));
+    }
+
     pub fn send_username_alert(&self, username: &str, id: i32, bad_term: &str) {
         // don't repeat alerts
         let config = self.config.load();
@@ -309,8 +333,21 @@ pub struct Config {
     pub server_alert_channel: u64,
     #[serde(default)]
     pub ticket_ping_channel: u64,
+    /// Maps game server regions to alert channels, unmapped regions use the default channels
+    #[serde(default)]
+    pub region_alert_channels: HashMap<String, u64>,
     #[serde(default)]
     pub oauth: OauthOptions,
+    /// Automatically whitelist linked users that are members of the main guild.
+    /// The whitelist is removed again when they unlink or leave, unless it was granted manually.
+    #[serde(default)]
+    pub auto_whitelist_members: bool,
+}
+
+impl Config {
+    fn region_alert_channel(&self, region: &str) -> Option<u64> {
+        self.region_alert_channels.get(region).copied().filter(|c| *c != 0)
+    }
 }
 
 impl ServerModule for DiscordModule {
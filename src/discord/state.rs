@@ -608,9 +608,23 @@ impl BotState {
             }
         }
 
+        // linked users keep the role they were given for linking
+        if let Some(role_id) = users.link_role_id()
+            && !new_roles_idx.contains(&role_id)
+            && let Some(role) = users.get_role(role_id)
+        {
+            new_roles.push(role.id.clone());
+            new_roles_idx.push(role_id);
+        }
+
         info!("Syncing roles for {} ({}): {:?}", user.username, db_user.account_id, new_roles);
 
         users.system_set_roles(db_user.account_id, &new_roles_idx).await?;
+
+        if self.config.load().auto_whitelist_members {
+            users.grant_discord_whitelist(db_user).await?;
+        }
+
         Ok(new_roles)
     }
 
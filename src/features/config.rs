@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -14,6 +17,18 @@ fn default_feature_cycle_interval() -> u32 {
     60 * 60 * 24 // 1 day
 }
 
+fn default_spreadsheet_debounce_secs() -> u32 {
+    10
+}
+
+fn default_min_feature_duration() -> u32 {
+    60 * 60 // 1 hour
+}
+
+fn default_max_feature_duration() -> u32 {
+    60 * 60 * 24 * 30 // 30 days
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
@@ -23,10 +38,20 @@ pub struct Config {
     pub database_pool_size: u32,
     #[serde(default = "default_feature_cycle_interval")]
     pub feature_cycle_interval: u32,
+    /// Shortest custom feature duration (in seconds) that can be set for a level
+    #[serde(default = "default_min_feature_duration")]
+    pub min_feature_duration: u32,
+    /// Longest custom feature duration (in seconds) that can be set for a level
+    #[serde(default = "default_max_feature_duration")]
+    pub max_feature_duration: u32,
     #[serde(default)]
     pub spreadsheet_id: Option<String>,
     #[serde(default)]
     pub google_credentials_path: Option<PathBuf>,
+    /// Spreadsheet updates are coalesced and written at most once per this many seconds.
+    /// 0 writes every change immediately. The flush interval is only read on startup.
+    #[serde(default = "default_spreadsheet_debounce_secs")]
+    pub spreadsheet_debounce_secs: u32,
     #[serde(default)]
     pub exhaust_notif_channel: u64,
     #[serde(default)]
@@ -35,6 +60,55 @@ pub struct Config {
     pub feature_notif_channel: u64,
     #[serde(default)]
     pub feature_notif_message: Option<String>,
+    /// Level IDs that can never be sent or queued for featuring
+    #[serde(default)]
+    pub blocked_level_ids: Vec<i32>,
+    /// If not empty, only these level IDs can be sent or queued for featuring
+    #[serde(default)]
+    pub allowed_level_ids: Vec<i32>,
+    /// Whether to check that a level exists on the GD servers before it can be sent or queued
+    #[serde(default)]
+    pub verify_sent_levels: bool,
+    /// Hour (0-23, UTC) at which quiet hours start. During quiet hours, Discord notifications
+    /// are held back and sent once they end. Both this and `quiet_hours_end` must be set.
+    #[serde(default)]
+    pub quiet_hours_start: Option<u8>,
+    /// Hour (0-23, UTC) at which quiet hours end
+    #[serde(default)]
+    pub quiet_hours_end: Option<u8>,
+}
+
+impl Config {
+    /// Whether the level ID passes the allow and block lists
+    pub fn is_level_allowed(&self, level_id: i32) -> bool {
+        !self.blocked_level_ids.contains(&level_id)
+            && (self.allowed_level_ids.is_empty() || self.allowed_level_ids.contains(&level_id))
+    }
+
+    /// If it's currently quiet hours, returns how long is left until they end
+    pub fn quiet_hours_remaining(&self) -> Option<Duration> {
+        const DAY: u64 = 86400;
+
+        let (Some(start), Some(end)) = (self.quiet_hours_start, self.quiet_hours_end) else {
+            return None;
+        };
+
+        let start = (start as u64 % 24) * 3600;
+        let end = (end as u64 % 24) * 3600;
+        if start == end {
+            return None;
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() % DAY;
+
+        let in_window = if start < end {
+            (start..end).contains(&now)
+        } else {
+            now >= start || now < end
+        };
+
+        in_window.then(|| Duration::from_secs((end + DAY - now) % DAY))
+    }
 }
 
 impl Default for Config {
@@ -43,12 +117,20 @@ impl Default for Config {
             database_url: default_database_url(),
             database_pool_size: default_database_pool_size(),
             feature_cycle_interval: default_feature_cycle_interval(),
+            min_feature_duration: default_min_feature_duration(),
+            max_feature_duration: default_max_feature_duration(),
             spreadsheet_id: None,
             google_credentials_path: None,
+            spreadsheet_debounce_secs: default_spreadsheet_debounce_secs(),
             exhaust_notif_channel: 0,
             exhaust_notif_message: None,
             feature_notif_channel: 0,
             feature_notif_message: None,
+            blocked_level_ids: Vec::new(),
+            allowed_level_ids: Vec::new(),
+            verify_sent_levels: false,
+            quiet_hours_start: None,
+            quiet_hours_end: None,
         }
     }
 }
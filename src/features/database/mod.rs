@@ -236,6 +236,29 @@ impl Db {
         Ok(())
     }
 
+    pub async fn remove_queued_level(&self, level_id: i32) -> DatabaseResult<()> {
+        let res = QueuedLevel::delete_by_id(level_id).exec(&self.conn).await?;
+
+        if res.rows_affected == 0 {
+            Err(DatabaseError::NotFound)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn remove_featured_level(&self, level_id: i32) -> DatabaseResult<()> {
+        let res = FeaturedLevel::delete_many()
+            .filter(featured_level::Column::LevelId.eq(level_id))
+            .exec(&self.conn)
+            .await?;
+
+        if res.rows_affected == 0 {
+            Err(DatabaseError::NotFound)
+        } else {
+            Ok(())
+        }
+    }
+
     pub async fn set_feature_duration(&self, level_id: i32, duration: i32) -> DatabaseResult<()> {
         if let Some(level) = FeaturedLevel::find()
             .filter(featured_level::Column::LevelId.eq(level_id))
@@ -11,11 +11,11 @@ use std::{
 use anyhow::anyhow;
 use arc_swap::ArcSwap;
 use server_shared::qunet::server::ServerHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
     core::{
-        gd_api::GDApiClient,
+        gd_api::{GDApiClient, GDApiFetchError},
         handler::ConnectionHandler,
         module::{ConfigurableModule, ModuleInitResult, ServerModule},
     },
@@ -32,7 +32,6 @@ use {
         discord::{DiscordMessage, DiscordModule, hex_color_to_decimal},
     },
     poise::serenity_prelude::{CreateEmbed, CreateEmbedAuthor},
-    tracing::warn,
 };
 
 mod config;
@@ -47,6 +46,12 @@ pub enum FeaturesError {
     Db(#[from] database::DatabaseError),
     #[error("Invalid feature tier: {0}")]
     InvalidTier(u8),
+    #[error("Level {0} is not allowed to be sent")]
+    LevelBlocked(i32),
+    #[error("Level {0} does not exist")]
+    LevelNotFound(i32),
+    #[error("Failed to fetch level from GD servers: {0}")]
+    Fetch(#[from] GDApiFetchError),
 }
 
 pub struct FeaturesModule {
@@ -56,14 +61,18 @@ pub struct FeaturesModule {
     active_level_tier: AtomicU8,
     active_level_edition: AtomicU32,
     sheets: Option<SheetsClient>,
+    /// Sheets that have changed and are waiting to be written, see `SHEET_*`
+    dirty_sheets: AtomicU8,
     #[cfg(feature = "discord")]
     discord: Option<Arc<DiscordModule>>,
     users_module: Arc<UsersModule>,
-
-    #[allow(unused)]
     gd_client: GDApiClient,
 }
 
+const SHEET_FEATURED: u8 = 1 << 0;
+const SHEET_QUEUED: u8 = 1 << 1;
+const SHEET_SENT: u8 = 1 << 2;
+
 pub struct FeaturedLevelMeta {
     pub id: i32,
     pub rate_tier: u8,
@@ -105,6 +114,19 @@ impl FeaturesModule {
             return Err(FeaturesError::InvalidTier(rate_tier));
         }
 
+        let verify = {
+            let config = self.config.load();
+            if !config.is_level_allowed(level_id) {
+                return Err(FeaturesError::LevelBlocked(level_id));
+            }
+
+            config.verify_sent_levels
+        };
+
+        if verify && self.gd_client.fetch_level(level_id).await?.is_none() {
+            return Err(FeaturesError::LevelNotFound(level_id));
+        }
+
         self.db
             .add_sent_level(
                 sender_id,
@@ -130,15 +152,64 @@ impl FeaturesModule {
         Ok(())
     }
 
+    pub async fn remove_queued_level(&self, level_id: i32) -> DatabaseResult<()> {
+        self.db.remove_queued_level(level_id).await?;
+        self.update_spreadsheet(false, true, false).await;
+
+        Ok(())
+    }
+
+    /// Removes a level from the featured list. If it was the active featured level,
+    /// the previously featured level becomes active again.
+    pub async fn remove_featured_level(&self, level_id: i32) -> DatabaseResult<()> {
+        self.db.remove_featured_level(level_id).await?;
+
+        if self.active_level.load(Ordering::Relaxed) == level_id {
+            let level = self.reload_featured_level().await?;
+            info!(
+                "Removed active featured level {level_id}, now featuring {}",
+                level.map_or(0, |l| l.level_id)
+            );
+        }
+
+        self.update_spreadsheet(true, false, false).await;
+
+        Ok(())
+    }
+
+    /// Returns the allowed range for custom feature durations
+    pub fn feature_duration_bounds(&self) -> (Duration, Duration) {
+        let config = self.config.load();
+        let min = Duration::from_secs(config.min_feature_duration as u64);
+        let max = Duration::from_secs(config.max_feature_duration as u64);
+
+        (min, max.max(min))
+    }
+
+    /// Sets the feature duration for a level, clamping it to the configured bounds.
+    /// A zero duration resets the level to the default cycle interval.
+    /// Returns the duration that was actually applied.
     pub async fn set_feature_duration(
         &self,
         level_id: i32,
         duration: Duration,
-    ) -> DatabaseResult<()> {
-        self.db.set_feature_duration(level_id, duration.as_secs() as i32).await?;
+    ) -> DatabaseResult<Duration> {
+        let (min, max) = self.feature_duration_bounds();
+
+        let applied = if duration.is_zero() { duration } else { duration.clamp(min, max) };
+
+        if applied != duration {
+            info!(
+                "Clamped feature duration for level {level_id} from {}s to {}s",
+                duration.as_secs(),
+                applied.as_secs()
+            );
+        }
+
+        self.db.set_feature_duration(level_id, applied.as_secs() as i32).await?;
         self.update_spreadsheet(true, true, false).await;
 
-        Ok(())
+        Ok(applied)
     }
 
     pub async fn set_feature_priority(&self, level_id: i32, priority: i32) -> DatabaseResult<()> {
@@ -264,12 +335,56 @@ impl FeaturesModule {
         }
     }
 
+    /// Marks the given sheets as changed. They are written by the periodic flush task,
+    /// or right away if debouncing is disabled.
     pub async fn update_spreadsheet(&self, featured: bool, queued: bool, sent: bool) {
+        if self.sheets.is_none() {
+            return;
+        }
+
+        if self.config.load().spreadsheet_debounce_secs == 0 {
+            self.update_spreadsheet_now(featured, queued, sent).await;
+            return;
+        }
+
+        let mut flags = 0;
+        if featured {
+            flags |= SHEET_FEATURED;
+        }
+        if queued {
+            flags |= SHEET_QUEUED;
+        }
+        if sent {
+            flags |= SHEET_SENT;
+        }
+
+        self.dirty_sheets.fetch_or(flags, Ordering::Relaxed);
+    }
+
+    /// Writes the given sheets immediately, bypassing the debounce
+    pub async fn update_spreadsheet_now(&self, featured: bool, queued: bool, sent: bool) {
         if let Err(e) = self.update_spreadsheet_inner(featured, queued, sent).await {
             error!("failed to update spreadsheet: {e}");
         }
     }
 
+    /// Writes all sheets that were changed since the last flush
+    async fn flush_spreadsheet(&self) {
+        let flags = self.dirty_sheets.swap(0, Ordering::Relaxed);
+        if flags == 0 {
+            return;
+        }
+
+        debug!("Flushing spreadsheet updates (flags: {flags:#b})");
+
+        self.update_spreadsheet_now(
+            flags & SHEET_FEATURED != 0,
+            flags & SHEET_QUEUED != 0,
+            flags & SHEET_SENT != 0,
+        )
+        .await;
+    }
+
     async fn update_spreadsheet_inner(
         &self,
         featured: bool,
@@ -331,7 +446,8 @@ impl FeaturesModule {
             .await?
             .map_or(GDDifficulty::NA, |l| l.difficulty);
 
-        discord.send_message(
+        self.send_discord_notification(
+            discord,
             config.feature_notif_channel,
             DiscordMessage::new()
                 .content(config.feature_notif_message.as_deref().unwrap_or_default())
@@ -360,7 +476,8 @@ impl FeaturesModule {
             return Ok(());
         }
 
-        discord.send_message(
+        self.send_discord_notification(
+            discord,
             config.exhaust_notif_channel,
             DiscordMessage::new()
                 .content(config.exhaust_notif_message.as_deref().unwrap_or_default()),
@@ -368,6 +485,30 @@ impl FeaturesModule {
 
         Ok(())
     }
+
+    /// Sends the message right away, or once quiet hours end if they are active
+    #[cfg(feature = "discord")]
+    fn send_discord_notification(
+        &self,
+        discord: &Arc<DiscordModule>,
+        channel: u64,
+        msg: DiscordMessage<'_>,
+    ) {
+        let Some(delay) = self.config.load().quiet_hours_remaining() else {
+            discord.send_message(channel, msg);
+            return;
+        };
+
+        debug!("Quiet hours are active, deferring discord notification by {delay:?}");
+
+        let discord = discord.clone();
+        let msg = msg.into_owned();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            discord.send_message(channel, msg);
+        });
+    }
 }
 
 impl ServerModule for FeaturesModule {
@@ -400,6 +541,7 @@ impl ServerModule for FeaturesModule {
             active_level_tier: AtomicU8::new(0),
             active_level_edition: AtomicU32::new(0),
             sheets,
+            dirty_sheets: AtomicU8::new(0),
             #[cfg(feature = "discord")]
             discord,
             users_module: handler.opt_module_owned::<UsersModule>().unwrap(),
@@ -412,6 +554,20 @@ impl ServerModule for FeaturesModule {
     }
 
     fn reload(&self, _server: &ServerHandle<ConnectionHandler>, config: Arc<config::Config>) {
+        let old = self.config.load();
+
+        // these are only read on startup
+        if old.database_url != config.database_url
+            || old.database_pool_size != config.database_pool_size
+            || old.spreadsheet_id != config.spreadsheet_id
+            || old.google_credentials_path != config.google_credentials_path
+            || old.spreadsheet_debounce_secs != config.spreadsheet_debounce_secs
+        {
+            warn!(
+                "Database or spreadsheet settings were changed, the server must be restarted to apply them"
+            );
+        }
+
         self.config.store(config);
     }
 
@@ -429,7 +585,13 @@ impl ServerModule for FeaturesModule {
         });
 
         server.schedule(Duration::from_hours(12), async |server| {
-            server.handler().module::<Self>().update_spreadsheet(true, true, true).await;
+            server.handler().module::<Self>().update_spreadsheet_now(true, true, true).await;
+        });
+
+        // also run when debouncing is disabled, in case it gets enabled by a reload
+        let debounce = self.config.load().spreadsheet_debounce_secs.max(1);
+        server.schedule(Duration::from_secs(debounce as u64), async |server| {
+            server.handler().module::<Self>().flush_spreadsheet().await;
         });
     }
 }
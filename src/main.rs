@@ -18,7 +18,7 @@ use std::{sync::Arc, time::Duration};
 use server_shared::qunet::{
     message::CompressionType,
     server::{
-        Server as QunetServer, ServerOutcome,
+        Server as QunetServer, ServerHandle, ServerOutcome,
         builder::{BufferPoolOpts, MemoryUsageOptions, ShouldCompressFn, UdpDiscoveryMode},
     },
     transport::compression::lz4_compress,
@@ -26,7 +26,7 @@ use server_shared::qunet::{
 
 use server_shared::config::parse_addr;
 use server_shared::logging::WorkerGuard;
-use tracing::{debug, error};
+use tracing::{debug, error, info};
 
 use crate::{
     auth::AuthModule,
@@ -133,7 +133,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut builder = QunetServer::builder()
         .with_memory_options(make_memory_limits(core.memory_usage))
-        .with_max_messages_per_second(16) // allow 16 messages, client does not really need more than this
+        // regular clients get a lower limit in the handler, this leaves room for staff tools
+        .with_max_messages_per_second(core.message_rate_limits.transport_limit() as _)
         .with_compression_determinator(make_compression_func(core.compression_level))
         .with_app_handler(handler);
 
@@ -250,7 +251,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Poll both of the servers
 
     tokio::select! {
+        _ = shutdown_signal() => {
+            info!("Received a shutdown signal, shutting down");
+            notify_shutdown(&server).await;
+            server.shutdown();
+            gs_server.shutdown();
+
+            if let Err(e) = srv_join_handle.await {
+                error!("Failed to join main server: {e}");
+            }
+
+            if let Err(e) = gs_srv_join_handle.await {
+                error!("Failed to join game server listener: {e}");
+            }
+        }
+
         _ = &mut srv_join_handle => {
+            // the server is already gone, there is nobody left to notify
             debug!("Main server has stopped, shutting down");
             gs_server.shutdown();
 
@@ -261,6 +278,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         _ = &mut gs_srv_join_handle => {
             debug!("Game server listener has stopped, shutting down");
+            notify_shutdown(&server).await;
             server.shutdown();
 
             if let Err(e) = srv_join_handle.await {
@@ -272,6 +290,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Resolves once the process is asked to stop with SIGINT, or SIGTERM on unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut sigterm = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+async fn notify_shutdown(server: &ServerHandle<ConnectionHandler>) {
+    server.handler().broadcast_shutdown("The server is restarting");
+
+    // give the messages a moment to actually get sent
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
 async fn init_module<T: ServerModule + ConfigurableModule>(
     handler: &mut ConnectionHandler,
 ) -> Arc<T> {
@@ -5,8 +5,9 @@ use std::{
 };
 
 use dashmap::DashMap;
-use nohash_hasher::BuildNoHashHasher;
+use nohash_hasher::{BuildNoHashHasher, IntSet};
 use parking_lot::{RawRwLock, RwLock, lock_api::RwLockReadGuard};
+use rustc_hash::FxHashMap;
 use thiserror::Error;
 use tracing::{debug, error, warn};
 
@@ -19,11 +20,15 @@ use crate::{
 pub enum RoomCreationError {
     #[error("room name is too long")]
     NameTooLong,
+    #[error("a room with this name already exists on this server")]
+    NameTaken,
 }
 
 pub struct RoomManager {
     rooms: DashMap<u32, Arc<Room>, BuildNoHashHasher<u32>>,
-    rooms_sorted: RwLock<BTreeSet<(usize, Arc<Room>)>>,
+    rooms_sorted: RwLock<BTreeSet<(bool, usize, Arc<Room>)>>,
+    /// Lowercase room name -> IDs of all rooms with that name
+    names: DashMap<String, IntSet<u32>>,
     global_room: Arc<Room>,
 }
 
@@ -40,6 +45,7 @@ impl RoomManager {
         Self {
             rooms: DashMap::default(),
             rooms_sorted: RwLock::new(BTreeSet::new()),
+            names: DashMap::new(),
             global_room,
         }
     }
@@ -62,7 +68,7 @@ impl RoomManager {
 
     pub(super) fn lock_sorted(
         &self,
-    ) -> RwLockReadGuard<'_, RawRwLock, BTreeSet<(usize, Arc<Room>)>> {
+    ) -> RwLockReadGuard<'_, RawRwLock, BTreeSet<(bool, usize, Arc<Room>)>> {
         self.rooms_sorted.read()
     }
 
@@ -72,8 +78,21 @@ impl RoomManager {
         passcode: u32,
         owner: i32,
         settings: RoomSettings,
+        unique_name: bool,
     ) -> Result<Arc<Room>, RoomCreationError> {
         let name = heapless::String::from_str(name).map_err(|_| RoomCreationError::NameTooLong)?;
+        let name_key = name.to_lowercase();
+
+        // hold the name entry for the whole creation, so two rooms with the same name can't race
+        let mut names = self.names.entry(name_key).or_default();
+
+        if unique_name
+            && names
+                .iter()
+                .any(|id| self.get(*id).is_some_and(|r| r.server_id() == settings.server_id))
+        {
+            return Err(RoomCreationError::NameTaken);
+        }
 
         loop {
             let id: u32 = rand::random_range(100000..1000000);
@@ -85,7 +104,8 @@ impl RoomManager {
                     let room = Arc::new(Room::new(id, owner, name, passcode, settings));
 
                     entry.insert(room.clone());
-                    self.rooms_sorted.write().insert((0, room.clone()));
+                    self.rooms_sorted.write().insert((false, 0, room.clone()));
+                    names.insert(id);
 
                     break Ok(room);
                 }
@@ -101,6 +121,10 @@ impl RoomManager {
         debug!("deleting room {id}");
         if let Some(room) = self.rooms.remove(&id).map(|entry| entry.1) {
             self.do_remove_from_sorted(&room, &mut self.rooms_sorted.write());
+            self.names.remove_if_mut(room.name.to_lowercase().as_str(), |_, ids| {
+                ids.remove(&id);
+                ids.is_empty()
+            });
             Some(room)
         } else {
             warn!("failed to delete non-existent room {id}");
@@ -114,8 +138,16 @@ impl RoomManager {
 
         self.do_remove_from_sorted(room, &mut sorted);
         let count = room.player_count();
+        let pinned = room.is_pinned();
         room.key_player_count.store(count, Ordering::Release);
-        sorted.insert((count, room.clone()));
+        room.key_pinned.store(pinned, Ordering::Release);
+        sorted.insert((pinned, count, room.clone()));
+    }
+
+    /// Pins or unpins the room, moving it to the top of the room list
+    pub(super) fn set_pinned(&self, room: &Arc<Room>, pinned: bool) {
+        room.set_pinned(pinned);
+        self.update_room_set(room);
     }
 
     /// Deletes all rooms from the manager. The global room remains intact, but all players are removed from it.
@@ -126,6 +158,7 @@ impl RoomManager {
 
         self.rooms.clear();
         self.rooms_sorted.write().clear();
+        self.names.clear();
 
         self.global_room.clear();
     }
@@ -134,9 +167,11 @@ impl RoomManager {
         let mut to_remove = Vec::new();
 
         for room in self.rooms.iter() {
-            room.cleanup_invites();
-
-            if room.player_count() == 0 && !room.is_global() {
+            if room.player_count() == 0
+                && !room.is_global()
+                && !room.is_persistent()
+                && !room.is_pinned()
+            {
                 to_remove.push(*room.key());
             }
         }
@@ -148,22 +183,46 @@ impl RoomManager {
     }
 
     #[allow(clippy::mutable_key_type)] // this is okay, because we use room ID as the secondary key, which is immutable
-    fn do_remove_from_sorted(&self, room: &Arc<Room>, sorted: &mut BTreeSet<(usize, Arc<Room>)>) {
+    fn do_remove_from_sorted(
+        &self,
+        room: &Arc<Room>,
+        sorted: &mut BTreeSet<(bool, usize, Arc<Room>)>,
+    ) {
         let kpc = room.key_player_count.load(Ordering::Acquire);
+        let kpin = room.key_pinned.load(Ordering::Acquire);
 
-        if !sorted.remove(&(kpc, room.clone())) {
+        if !sorted.remove(&(kpin, kpc, room.clone())) {
             error!(
-                "internal inconsistency: key ({}, {}) couldn't be found in the sorted rooms set",
-                kpc, room.id
+                "internal inconsistency: key ({}, {}, {}) couldn't be found in the sorted rooms set",
+                kpin, kpc, room.id
             );
         }
     }
 
+    /// Calls the function for every room including the global room, for periodic maintenance
+    pub(super) fn for_each_room(&self, mut f: impl FnMut(&Arc<Room>)) {
+        f(&self.global_room);
+        iter_dashmap(&self.rooms, |(_id, room)| f(room));
+    }
+
+    /// Returns how many joinable rooms each game server is hosting
+    pub(super) fn count_rooms_by_server(&self) -> FxHashMap<u8, u32> {
+        let mut out = FxHashMap::default();
+
+        iter_dashmap(&self.rooms, |(_id, room)| {
+            if !room.is_global() && room.is_joinable() {
+                *out.entry(room.server_id()).or_default() += 1;
+            }
+        });
+
+        out
+    }
+
     pub(super) fn get_all_rooms_on_server(&self, server_id: u8) -> Vec<Arc<Room>> {
         let mut out = Vec::new();
 
         iter_dashmap(&self.rooms, |(_id, room)| {
-            if room.settings.lock().server_id == server_id {
+            if room.server_id() == server_id {
                 out.push(room.clone());
             }
         });
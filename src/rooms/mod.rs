@@ -1,4 +1,7 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     core::{
@@ -9,10 +12,12 @@ use crate::{
     },
     rooms::invite_token::InviteToken,
 };
-use rustc_hash::FxHashSet;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
 use serde::{Deserialize, Serialize};
 use server_shared::qunet::server::ServerHandle;
-use tracing::info;
+use tracing::{info, warn};
 
 mod invite_token;
 mod manager;
@@ -28,6 +33,20 @@ pub use settings::RoomSettings;
 
 pub struct RoomModule {
     manager: RoomManager,
+    /// Rooms created from the config, along with the string ID of the game server hosting them
+    default_rooms: Mutex<Vec<(String, Arc<Room>)>>,
+    /// Last non-global room of each disconnected player, so they can rejoin it after reconnecting
+    last_rooms: DashMap<i32, LastRoom>,
+    config: Arc<Config>,
+}
+
+const MAX_ROOM_NAME_LEN: usize = 64;
+
+struct LastRoom {
+    room_id: u32,
+    original_owner: i32,
+    passcode: u32,
+    left_at: Instant,
 }
 
 impl RoomModule {
@@ -39,6 +58,11 @@ impl RoomModule {
         self.manager.get_or_global(id)
     }
 
+    /// Returns the ID of the game server that hosts the given room
+    pub fn get_room_server(&self, room_id: u32) -> Option<u8> {
+        self.get_room(room_id).map(|r| r.server_id())
+    }
+
     pub fn global_room(&self) -> Arc<Room> {
         self.manager.global()
     }
@@ -51,6 +75,62 @@ impl RoomModule {
         self.manager.room_count()
     }
 
+    /// Returns how many joinable rooms each game server is hosting, keyed by server ID
+    pub fn room_counts_by_server(&self) -> FxHashMap<u8, u32> {
+        self.manager.count_rooms_by_server()
+    }
+
+    /// Whether sessions in custom rooms must be on the game server that hosts the room
+    pub fn enforce_room_server(&self) -> bool {
+        self.config.enforce_room_server
+    }
+
+    /// Returns the first blocklist pattern that the room name contains, if any
+    pub fn blocked_name_pattern(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+
+        self.config
+            .room_name_blocklist
+            .iter()
+            .find(|p| !p.is_empty() && name.contains(&p.to_ascii_lowercase()))
+            .map(|p| p.as_str())
+    }
+
+    /// Prepends the configured prefix to the room name, truncating the user-provided portion
+    /// so that the result still fits in a room name
+    pub fn apply_name_prefix(&self, name: &str) -> String {
+        let prefix = &self.config.room_name_prefix;
+        if prefix.is_empty() {
+            return name.to_owned();
+        }
+
+        let available = MAX_ROOM_NAME_LEN.saturating_sub(prefix.len());
+        let mut end = name.len().min(available);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let mut out = String::with_capacity(prefix.len() + end);
+        out.push_str(prefix);
+        out.push_str(&name[..end]);
+        out
+    }
+
+    /// How long an invite token stays valid after being created
+    pub fn invite_lifetime(&self) -> Duration {
+        Duration::from_secs(self.config.invite_lifetime_secs)
+    }
+
+    /// Maximum amount of players sent to a client that requests the players in the global room
+    pub fn global_room_player_cap(&self) -> usize {
+        self.config.global_room_player_cap
+    }
+
+    /// Minimum time between two rooms created by the same player
+    pub fn room_create_cooldown(&self) -> Duration {
+        Duration::from_secs(self.config.room_create_cooldown_secs)
+    }
+
     pub fn create_room(
         &self,
         name: &str,
@@ -58,7 +138,13 @@ impl RoomModule {
         owner: i32,
         settings: RoomSettings,
     ) -> Result<Arc<Room>, RoomCreationError> {
-        self.manager.create_room(name, passcode, owner, settings)
+        self.manager.create_room(
+            name,
+            passcode,
+            owner,
+            settings,
+            self.config.enforce_unique_room_names,
+        )
     }
 
     pub async fn create_room_and_join(
@@ -89,7 +175,15 @@ impl RoomModule {
             room
         } else {
             let room = self.get_room(room_id).ok_or(data::RoomJoinFailedReason::NotFound)?;
-            self.join_room(client, gsm, room.clone(), passcode).await?;
+
+            // players rejoining their last room after reconnecting don't need the passcode again
+            let last_passcode = self.last_room_passcode(client, &room);
+            self.join_room(client, gsm, room.clone(), last_passcode.unwrap_or(passcode)).await?;
+
+            if last_passcode.is_some() {
+                self.last_rooms.remove(&client.account_id());
+            }
+
             room
         };
 
@@ -110,7 +204,8 @@ impl RoomModule {
         }
 
         let room = self.get_room(room_id).ok_or(data::RoomJoinFailedReason::NotFound)?;
-        if !room.consume_invite_token(token) {
+
+        if !room.consume_invite_token(token, self.invite_lifetime()) {
             return Err(data::RoomJoinFailedReason::NotFound);
         }
 
@@ -132,7 +227,27 @@ impl RoomModule {
             return Ok(());
         }
 
-        let handle = room.add_player(client.clone(), passcode)?;
+        let lockout = Duration::from_secs(self.config.passcode_lockout_secs);
+        let max_attempts = self.config.max_passcode_attempts;
+
+        // locked out clients are told the passcode is wrong, even if it is correct this time
+        if max_attempts != 0 && client.is_passcode_locked_out(room.id, max_attempts, lockout) {
+            return Err(data::RoomJoinFailedReason::InvalidPasscode);
+        }
+
+        let handle = match room.add_player(client.clone(), passcode) {
+            Ok(handle) => handle,
+            Err(data::RoomJoinFailedReason::InvalidPasscode) => {
+                client.record_passcode_failure(room.id);
+                return Err(data::RoomJoinFailedReason::InvalidPasscode);
+            }
+            Err(e) => return Err(e),
+        };
+
+        if room.has_password() {
+            client.clear_passcode_failures(room.id);
+        }
+
         self.clear_client_room(client, gsm).await; // leave after adding to the new room, since it can fail
         self.set_client_room(client, handle).await;
 
@@ -174,10 +289,98 @@ impl RoomModule {
         info!("Closing {} rooms on server {server_id}", rooms.len());
 
         for room in rooms {
+            // default rooms are kept around until the server reconnects
+            if room.is_persistent() {
+                room.make_unjoinable();
+            }
+
             self.close_room_arc(room, gsm).await;
         }
     }
 
+    /// Announces all rooms on the given server to it again, should be called when the server
+    /// reconnects within the grace period. Rooms that can't be recreated are closed.
+    pub async fn rehost_rooms_on_server(&self, server_id: u8, gsm: &GameServerManager) {
+        let rooms = self.manager.get_all_rooms_on_server(server_id);
+        info!("Re-hosting {} rooms on server {server_id}", rooms.len());
+
+        for room in rooms {
+            if let Err(e) =
+                gsm.notify_room_created(server_id, room.id, room.passcode, room.owner()).await
+            {
+                warn!("Failed to re-host room {} on server {server_id}: {e}", room.id);
+
+                if room.is_persistent() {
+                    room.make_unjoinable();
+                }
+
+                self.close_room_arc(room, gsm).await;
+            }
+        }
+    }
+
+    /// Creates all the default rooms that are assigned to the given game server on it,
+    /// should be called once the server connects
+    pub async fn host_default_rooms(
+        &self,
+        server_id: u8,
+        string_id: &str,
+        gsm: &GameServerManager,
+    ) {
+        let rooms: Vec<_> = self
+            .default_rooms
+            .lock()
+            .iter()
+            .filter(|(sid, _)| sid == string_id)
+            .map(|(_, room)| room.clone())
+            .collect();
+
+        for room in rooms {
+            room.settings.lock().server_id = server_id;
+
+            match gsm.notify_room_created(server_id, room.id, room.passcode, room.owner()).await {
+                Ok(()) => {
+                    info!(
+                        "Default room '{}' ({}) is now hosted on {string_id}",
+                        room.name, room.id
+                    );
+                    room.make_joinable();
+                }
+
+                Err(e) => {
+                    warn!("Failed to create default room '{}' on {string_id}: {e}", room.name);
+                }
+            }
+        }
+    }
+
+    fn create_default_rooms(&self, config: &Config) {
+        let mut default_rooms = self.default_rooms.lock();
+
+        for def in &config.default_rooms {
+            let settings = RoomSettings {
+                player_limit: def.player_limit,
+                faster_reset: def.faster_reset,
+                teams: def.teams,
+                collision: def.collision,
+                initial_teams: def.initial_teams.clone(),
+                ..Default::default()
+            };
+
+            // owned by the system (account 0), so that no player can ever manage or close them
+            match self.create_room(&def.name, 0, 0, settings) {
+                Ok(room) => {
+                    // not joinable until the game server hosting it connects
+                    room.make_unjoinable();
+                    room.make_persistent();
+                    default_rooms.push((def.server.clone(), room));
+                }
+
+                Err(e) => warn!("Failed to create default room '{}': {e}", def.name),
+            }
+        }
+    }
+
     async fn close_room_arc(
         &self,
         room: Arc<Room>,
@@ -212,25 +415,74 @@ impl RoomModule {
             sorted
                 .iter()
                 .rev()
-                .filter(|x| filter(&x.1))
+                .filter(|x| filter(&x.2))
                 .skip(skip)
                 .take(count)
-                .map(|x| x.1.clone())
+                .map(|x| x.2.clone())
                 .collect(),
             sorted.len(),
         )
     }
 
+    /// Pins or unpins a room, returns `false` if the room does not exist
+    pub fn set_room_pinned(&self, room_id: u32, pinned: bool) -> bool {
+        match self.get_room(room_id) {
+            Some(room) => {
+                self.manager.set_pinned(&room, pinned);
+                true
+            }
+
+            None => false,
+        }
+    }
+
     pub fn get_friend_rooms(&self, friends: &FxHashSet<i32>) -> Vec<Arc<Room>> {
         self.manager
             .lock_sorted()
             .iter()
-            .filter(|x| friends.contains(&x.1.owner()))
-            .map(|x| x.1.clone())
+            .filter(|x| friends.contains(&x.2.owner()))
+            .map(|x| x.2.clone())
             .collect()
     }
 
+    /// Returns the passcode of the room, if it is the room the client was in when they last disconnected.
+    /// The usual join checks still apply, so rejoining fails if the room is now full or the player was banned.
+    fn last_room_passcode(&self, client: &ClientStateHandle, room: &Room) -> Option<u32> {
+        let last = self.last_rooms.get(&client.account_id())?;
+
+        if last.room_id != room.id
+            || last.left_at.elapsed() > Duration::from_secs(self.config.last_room_ttl_secs)
+        {
+            return None;
+        }
+
+        // the room ID could have been reused by a different room since then,
+        // which would have been created after the player left
+        if room.original_owner != last.original_owner
+            || room.since_creation() < last.left_at.elapsed()
+        {
+            return None;
+        }
+
+        Some(last.passcode)
+    }
+
     pub async fn cleanup_player(&self, client: &ClientStateHandle, gsm: &GameServerManager) {
+        if self.config.last_room_ttl_secs != 0
+            && let Some(room) = client.get_room()
+            && !room.is_global()
+        {
+            self.last_rooms.insert(
+                client.account_id(),
+                LastRoom {
+                    room_id: room.id,
+                    original_owner: room.original_owner,
+                    passcode: room.passcode,
+                    left_at: Instant::now(),
+                },
+            );
+        }
+
         self.clear_client_room(client, gsm).await;
     }
 
@@ -243,9 +495,9 @@ impl RoomModule {
             if !room.is_global() {
                 let player_count = room.player_count();
 
-                if player_count == 0 {
+                if player_count == 0 && !room.is_persistent() {
                     self.manager.remove_room(room.id);
-                    let server_id = room.settings.lock().server_id;
+                    let server_id = room.server_id();
                     let _ = gsm.notify_room_deleted(server_id, room.id).await;
                 } else {
                     self.manager.update_room_set(&room);
@@ -266,17 +518,139 @@ impl RoomModule {
         }
     }
 
+    /// Removes expired invite tokens from all rooms
+    pub fn cleanup_all_invites(&self) {
+        let lifetime = self.invite_lifetime();
+        self.manager.for_each_room(|room| room.cleanup_invites(lifetime));
+    }
+
     pub fn routine_cleanup(&self) {
         self.manager.routine_cleanup();
+
+        let ttl = Duration::from_secs(self.config.last_room_ttl_secs);
+        self.last_rooms.retain(|_, last| last.left_at.elapsed() <= ttl);
     }
 }
 
-#[derive(Deserialize, Serialize, Default)]
-pub struct Config {}
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DefaultRoom {
+    pub name: String,
+    /// String ID of the game server that will host this room
+    pub server: String,
+    #[serde(default)]
+    pub player_limit: u16,
+    #[serde(default)]
+    pub faster_reset: bool,
+    #[serde(default)]
+    pub teams: bool,
+    #[serde(default)]
+    pub collision: bool,
+    /// Colors of the teams the room starts with. If empty, the room has a single default team.
+    #[serde(default)]
+    pub initial_teams: Vec<u32>,
+}
+
+fn default_max_passcode_attempts() -> u32 {
+    5
+}
+
+fn default_passcode_lockout_secs() -> u64 {
+    60
+}
+
+fn default_enforce_room_server() -> bool {
+    true
+}
+
+fn default_room_create_cooldown_secs() -> u64 {
+    15
+}
+
+fn default_invite_lifetime_secs() -> u64 {
+    60 * 15
+}
+
+fn default_global_room_player_cap() -> usize {
+    100
+}
+
+fn default_last_room_ttl_secs() -> u64 {
+    1800
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Config {
+    /// How many times a player can enter a wrong room passcode before they are temporarily locked out
+    /// of joining that room. 0 disables the limit.
+    #[serde(default = "default_max_passcode_attempts")]
+    pub max_passcode_attempts: u32,
+    /// How long (in seconds) after the last failed attempt the lockout lasts
+    #[serde(default = "default_passcode_lockout_secs")]
+    pub passcode_lockout_secs: u64,
+    /// Public rooms that are created on startup and always exist, even when empty.
+    /// They become joinable once their game server connects.
+    #[serde(default)]
+    pub default_rooms: Vec<DefaultRoom>,
+    /// Whether to disallow creating a room with the same name (case-insensitive) as another room
+    /// on the same game server
+    #[serde(default)]
+    pub enforce_unique_room_names: bool,
+    /// How long (in seconds) after disconnecting a player can still rejoin their last room.
+    /// 0 disables remembering the last room.
+    #[serde(default = "default_last_room_ttl_secs")]
+    pub last_room_ttl_secs: u64,
+    /// How long (in seconds) a player has to wait between creating rooms. Staff are exempt.
+    /// 0 disables the cooldown.
+    #[serde(default = "default_room_create_cooldown_secs")]
+    pub room_create_cooldown_secs: u64,
+    /// Whether to reject joining a level on a different game server than the one hosting
+    /// the player's current room
+    #[serde(default = "default_enforce_room_server")]
+    pub enforce_room_server: bool,
+    /// How long (in seconds) a room invite stays valid after being sent
+    #[serde(default = "default_invite_lifetime_secs")]
+    pub invite_lifetime_secs: u64,
+    /// How many players at most are sent to a client requesting the players in the global room.
+    /// Higher values show more players at once, at the cost of larger messages.
+    #[serde(default = "default_global_room_player_cap")]
+    pub global_room_player_cap: usize,
+    /// Text prepended to the names of all rooms created by players, e.g. a region tag.
+    /// The player's portion of the name gets truncated if the result would be too long.
+    #[serde(default)]
+    pub room_name_prefix: String,
+    /// Case-insensitive substrings that are not allowed in room names, in addition to
+    /// the word filter. Applies to everyone except trusted users.
+    #[serde(default)]
+    pub room_name_blocklist: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_passcode_attempts: default_max_passcode_attempts(),
+            passcode_lockout_secs: default_passcode_lockout_secs(),
+            default_rooms: Vec::new(),
+            enforce_unique_room_names: false,
+            last_room_ttl_secs: default_last_room_ttl_secs(),
+            room_create_cooldown_secs: default_room_create_cooldown_secs(),
+            enforce_room_server: default_enforce_room_server(),
+            invite_lifetime_secs: default_invite_lifetime_secs(),
+            global_room_player_cap: default_global_room_player_cap(),
+            room_name_prefix: String::new(),
+            room_name_blocklist: Vec::new(),
+        }
+    }
+}
 
 impl ServerModule for RoomModule {
-    async fn new(_config: Arc<Config>, _handler: &ConnectionHandler) -> ModuleInitResult<Self> {
-        Ok(Self { manager: RoomManager::new() })
+    async fn new(config: Arc<Config>, _handler: &ConnectionHandler) -> ModuleInitResult<Self> {
+        Ok(Self {
+            manager: RoomManager::new(),
+            default_rooms: Mutex::new(Vec::new()),
+            last_rooms: DashMap::new(),
+            config,
+        })
     }
 
     fn id() -> &'static str {
@@ -288,9 +662,15 @@ impl ServerModule for RoomModule {
     }
 
     fn on_launch(&self, server: &ServerHandle<ConnectionHandler>) {
+        self.create_default_rooms(&self.config);
+
         server.schedule(Duration::from_mins(30), async |server| {
             server.handler().module::<RoomModule>().routine_cleanup();
         });
+
+        server.schedule(Duration::from_mins(5), async |server| {
+            server.handler().module::<RoomModule>().cleanup_all_invites();
+        });
     }
 }
 
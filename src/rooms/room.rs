@@ -20,7 +20,6 @@ use crate::{
 };
 
 pub const MAX_TEAM_COUNT: usize = 100;
-pub const INVITE_LIFETIME: Duration = Duration::from_mins(15);
 
 #[derive(Clone)]
 pub struct RoomPlayer {
@@ -77,7 +76,13 @@ pub struct Room {
     players: RwLock<Slab<RoomPlayer>>,
     player_count: AtomicUsize,
     pub(super) key_player_count: AtomicUsize,
+    pub(super) key_pinned: AtomicBool,
     joinable: AtomicBool,
+    /// Persistent rooms are not deleted when they become empty
+    persistent: AtomicBool,
+    /// Pinned rooms are always shown at the top of the room list
+    pinned: AtomicBool,
+    teams_dirty: AtomicBool,
 }
 
 impl Room {
@@ -86,8 +91,15 @@ impl Room {
         owner: i32,
         name: RoomNameString,
         passcode: u32,
-        settings: RoomSettings,
+        mut settings: RoomSettings,
     ) -> Self {
+        let initial_teams = std::mem::take(&mut settings.initial_teams);
+        let teams: SmallVec<[RoomTeam; 8]> = if initial_teams.is_empty() {
+            SmallVec::from_elem(RoomTeam::new(0xffffffff), 1)
+        } else {
+            initial_teams.into_iter().take(MAX_TEAM_COUNT).map(RoomTeam::new).collect()
+        };
+
         Self {
             id,
             owner: AtomicI32::new(owner),
@@ -96,14 +108,18 @@ impl Room {
             settings: Mutex::new(settings),
             pinned_level: AtomicU64::from(0),
             passcode,
-            teams: RwLock::new(SmallVec::from_elem(RoomTeam::new(0xffffffff), 1)),
+            teams: RwLock::new(teams),
             banned: RwLock::new(SmallVec::new()),
             invite_tokens: Mutex::new(SmallVec::new()),
             created_at: Instant::now(),
             players: RwLock::new(Slab::new()),
             player_count: AtomicUsize::new(0),
             key_player_count: AtomicUsize::new(0),
+            key_pinned: AtomicBool::new(false),
             joinable: AtomicBool::new(true),
+            persistent: AtomicBool::new(false),
+            pinned: AtomicBool::new(false),
+            teams_dirty: AtomicBool::new(false),
         }
     }
 
@@ -237,6 +253,40 @@ impl Room {
         self.joinable.store(false, Ordering::Relaxed);
     }
 
+    pub(super) fn make_joinable(&self) {
+        self.joinable.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_joinable(&self) -> bool {
+        self.joinable.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn make_persistent(&self) {
+        self.persistent.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.persistent.load(Ordering::Relaxed)
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned.load(Ordering::Relaxed)
+    }
+
+    /// Must be followed by updating the room set, so that the room is moved in the room list
+    pub(super) fn set_pinned(&self, pinned: bool) {
+        self.pinned.store(pinned, Ordering::Relaxed);
+    }
+
+    /// Marks the teams as changed, returns `false` if they were already marked
+    pub fn mark_teams_dirty(&self) -> bool {
+        !self.teams_dirty.swap(true, Ordering::AcqRel)
+    }
+
+    pub fn clear_teams_dirty(&self) {
+        self.teams_dirty.store(false, Ordering::Release);
+    }
+
     pub fn has_player(&self, player: &ClientStateHandle) -> bool {
         player.get_room_id().is_some_and(|id| id == self.id)
     }
@@ -293,6 +343,16 @@ impl Room {
         self.settings.lock().private_invites
     }
 
+    pub fn server_id(&self) -> u8 {
+        self.settings.lock().server_id
+    }
+
+    /// Whether levels in this room can be played on the given game server.
+    /// Custom rooms are bound to the server hosting them, the global room can use any server.
+    pub fn allows_server(&self, server_id: u8) -> bool {
+        self.is_global() || self.server_id() == server_id
+    }
+
     pub fn ban_player(&self, id: i32) {
         let mut players = self.banned.write();
         if players.len() > 256 {
@@ -311,10 +371,16 @@ impl Room {
         }
     }
 
-    pub fn consume_invite_token(&self, token: InviteToken) -> bool {
+    /// Uses up the token, returns `false` if it does not exist or is older than `lifetime`
+    pub fn consume_invite_token(&self, token: InviteToken, lifetime: Duration) -> bool {
         let mut tokens = self.invite_tokens.lock();
 
         match tokens.binary_search_by_key(&token, |t| t.token) {
+            Ok(pos) if tokens[pos].created_at.elapsed() >= lifetime => {
+                tokens.remove(pos);
+                false
+            }
+
             Ok(pos) => {
                 tokens.remove(pos);
                 true
@@ -359,8 +425,9 @@ impl Room {
         }
     }
 
-    pub fn cleanup_invites(&self) {
-        self.invite_tokens.lock().retain(|inv| inv.created_at.elapsed() < INVITE_LIFETIME);
+    /// Removes invite tokens older than `lifetime`
+    pub fn cleanup_invites(&self, lifetime: Duration) {
+        self.invite_tokens.lock().retain(|inv| inv.created_at.elapsed() < lifetime);
     }
 
     pub fn is_banned(&self, id: i32) -> bool {
@@ -541,3 +608,127 @@ impl Deref for ClientRoomHandle {
         &self.room
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // these only cover what a room can do without players in it,
+    // adding players needs a real client handle
+
+    fn test_room(owner: i32, passcode: u32, settings: RoomSettings) -> Room {
+        Room::new(123456, owner, "Test Room".try_into().unwrap(), passcode, settings)
+    }
+
+    #[test]
+    fn create_room() {
+        let room = test_room(1, 0, RoomSettings::default());
+
+        assert_eq!(room.owner(), 1);
+        assert_eq!(room.original_owner, 1);
+        assert_eq!(room.player_count(), 0);
+        assert_eq!(room.team_count(), 1);
+        assert!(room.is_joinable());
+        assert!(!room.is_global());
+        assert!(!room.has_password());
+
+        assert!(test_room(1, 1234, RoomSettings::default()).has_password());
+    }
+
+    #[test]
+    fn initial_teams_are_capped() {
+        let room = test_room(
+            1,
+            0,
+            RoomSettings {
+                initial_teams: (0..MAX_TEAM_COUNT as u32 + 10).collect(),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(room.team_count(), MAX_TEAM_COUNT);
+        assert!(room.settings.lock().initial_teams.is_empty());
+        assert!(room.create_team(None).is_err());
+    }
+
+    #[test]
+    fn sessions_must_match_room_server() {
+        let room = test_room(
+            1,
+            0,
+            RoomSettings {
+                server_id: 2,
+                ..Default::default()
+            },
+        );
+
+        assert!(room.allows_server(2));
+        assert!(!room.allows_server(3));
+        assert!(!room.allows_server(0));
+
+        let global = Room::new(0, 0, "Global".try_into().unwrap(), 0, RoomSettings::default());
+        assert!(global.allows_server(0));
+        assert!(global.allows_server(3));
+    }
+
+    #[test]
+    fn invite_tokens_are_single_use() {
+        let room = test_room(1, 0, RoomSettings::default());
+        let lifetime = Duration::from_secs(60);
+
+        let token = room.create_invite_token();
+        assert_eq!(token.room_id(), room.id);
+
+        assert!(room.consume_invite_token(token, lifetime));
+        assert!(!room.consume_invite_token(token, lifetime));
+        assert!(!room.consume_invite_token(InviteToken(0), lifetime));
+    }
+
+    #[test]
+    fn expired_invite_tokens_are_rejected() {
+        let room = test_room(1, 0, RoomSettings::default());
+
+        let token = room.create_invite_token();
+        assert!(!room.consume_invite_token(token, Duration::ZERO));
+
+        // expired tokens are removed when they are rejected
+        assert!(!room.consume_invite_token(token, Duration::from_secs(60)));
+
+        let token = room.create_invite_token();
+        room.cleanup_invites(Duration::ZERO);
+        assert!(!room.consume_invite_token(token, Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn bans() {
+        let room = test_room(1, 0, RoomSettings::default());
+
+        for id in [5, 3, 9, 3] {
+            room.ban_player(id);
+        }
+
+        assert!(room.is_banned(3));
+        assert!(room.is_banned(5));
+        assert!(room.is_banned(9));
+        assert!(!room.is_banned(4));
+        assert_eq!(room.banned.read().as_slice(), [3, 5, 9]);
+    }
+
+    #[test]
+    fn team_management() {
+        let room = test_room(1, 0, RoomSettings::default());
+
+        // the last remaining team can't be deleted
+        assert!(room.delete_team(0).is_err());
+
+        assert_eq!(room.create_team(0x112233ff).unwrap(), 2);
+        assert_eq!(room.create_team(None).unwrap(), 3);
+
+        assert!(room.set_team_color(1, 0x445566ff));
+        assert!(!room.set_team_color(3, 0x445566ff));
+
+        assert!(room.delete_team(3).is_err());
+        assert!(room.delete_team(1).unwrap().is_empty());
+        assert_eq!(room.team_count(), 2);
+    }
+}
@@ -21,6 +21,10 @@ pub struct RoomSettings {
     pub two_player_mode: bool,
     pub deathlink: bool,
     pub switcheroo: bool,
+
+    /// Colors of the teams the room starts with, only used when the room is created.
+    /// Not sent by clients, only set for default rooms from the config.
+    pub initial_teams: Vec<u32>,
 }
 
 impl RoomSettings {
@@ -41,6 +45,8 @@ impl RoomSettings {
             two_player_mode: reader.get_two_player_mode(),
             deathlink: reader.get_deathlink(),
             switcheroo: reader.get_switcheroo(),
+
+            initial_teams: Vec::new(),
         })
     }
 
@@ -10,6 +10,14 @@ fn default_database_pool_size() -> u32 {
     5
 }
 
+fn default_db_required() -> bool {
+    true
+}
+
+fn default_admin_fetch_cache_secs() -> u64 {
+    5
+}
+
 fn default_roles() -> Vec<Role> {
     vec![]
 }
@@ -28,6 +36,18 @@ fn default_check_usernames() -> bool {
     true
 }
 
+fn default_username_history_limit() -> u32 {
+    10
+}
+
+fn default_welcome_grace_days() -> u32 {
+    14
+}
+
+fn default_reply_cooldown_ms() -> u32 {
+    3000
+}
+
 fn default_mute_reasons() -> Vec<String> {
     vec![]
 }
@@ -74,6 +94,9 @@ pub struct Role {
     pub can_rate_features: Option<bool>,
     #[serde(default)]
     pub can_name_rooms: Option<bool>,
+    /// Overrides `reply_cooldown_ms` for users with this role
+    #[serde(default)]
+    pub reply_cooldown_ms: Option<u32>,
 }
 
 impl Role {
@@ -93,6 +116,20 @@ pub struct PunishReasons {
     pub room_ban: Vec<String>,
 }
 
+/// Maximum amount of each action a single moderator can perform per day (UTC). 0 means unlimited.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ActionQuotas {
+    #[serde(default)]
+    pub bans: u32,
+    #[serde(default)]
+    pub mutes: u32,
+    #[serde(default)]
+    pub room_bans: u32,
+    #[serde(default)]
+    pub role_edits: u32,
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
@@ -100,6 +137,14 @@ pub struct Config {
     pub database_url: String,
     #[serde(default = "default_database_pool_size")]
     pub database_pool_size: u32,
+    /// Whether the server should refuse to start if the database is unreachable. If disabled, the
+    /// server starts without moderation and persistence, and keeps retrying the connection.
+    #[serde(default = "default_db_required")]
+    pub db_required: bool,
+    /// How long (in seconds) users fetched by moderators are cached for. Any change to a user
+    /// invalidates their cached entry. 0 disables the cache.
+    #[serde(default = "default_admin_fetch_cache_secs")]
+    pub admin_fetch_cache_secs: u64,
     #[serde(default = "default_roles")]
     pub roles: Vec<Role>,
     #[serde(default = "default_super_admins")]
@@ -108,8 +153,19 @@ pub struct Config {
     pub script_sign_key: String,
     #[serde(default)]
     pub whitelist: bool,
+    /// Accounts that bypass the word filter and per-message rate limits. Bans and mutes still apply.
+    #[serde(default)]
+    pub trusted_accounts: Vec<i32>,
     #[serde(default)]
     pub vc_requires_discord_link: bool,
+    /// ID of the role that is automatically given to users that link their Discord account,
+    /// and removed when they unlink it. Leave empty to disable.
+    #[serde(default)]
+    pub auto_role_on_link: String,
+    /// Reject logins from users that share a (non-whitelisted) uident with a currently banned account.
+    /// Disabled by default, as uident collisions can lead to false positives.
+    #[serde(default)]
+    pub enforce_uident_ban_evasion: bool,
     /// Disallows regular players from being able to name rooms, instead requires them to have the appropriate role permission.
     #[serde(default)]
     pub disallow_room_names: bool,
@@ -122,6 +178,43 @@ pub struct Config {
     /// How long to keep player count records in the database, in days. 0 means to keep them indefinitely.
     #[serde(default)]
     pub player_count_retention_days: u32,
+    /// How many previous usernames to remember per user. 0 disables username history.
+    #[serde(default = "default_username_history_limit")]
+    pub username_history_limit: u32,
+    /// Create a database entry for every player the first time they log in. Off by default,
+    /// without this entries are only created for players that are punished, given roles or edited by staff.
+    /// Note that enabling this makes the user table grow by one row for every player that ever connects.
+    /// Welcome notices and alerts are only sent while this is enabled.
+    #[serde(default)]
+    pub create_users_on_login: bool,
+    /// How many days `create_users_on_login` must have been enabled before players are greeted as new.
+    /// Players that connected before it was enabled have no entry either, this avoids greeting all of them at once.
+    #[serde(default = "default_welcome_grace_days")]
+    pub welcome_grace_days: u32,
+    /// Notice shown to players the first time they ever log in. Leave empty to disable.
+    #[serde(default)]
+    pub welcome_message: String,
+    /// Whether to send a Discord alert when a player logs in for the first time.
+    #[serde(default)]
+    pub welcome_discord_alert: bool,
+
+    /// Minimum time between two notice replies from the same user, in milliseconds.
+    /// Can be overridden per role.
+    #[serde(default = "default_reply_cooldown_ms")]
+    pub reply_cooldown_ms: u32,
+
+    /// Daily limits on destructive actions per moderator, to limit the damage a compromised
+    /// staff account can do. Super admins are exempt.
+    #[serde(default)]
+    pub daily_action_quotas: ActionQuotas,
+
+    /// Roles that are automatically removed from users who have not been seen for `inactive_staff_days`.
+    /// Super admins are never demoted.
+    #[serde(default)]
+    pub inactive_staff_roles: Vec<String>,
+    /// After how many days of inactivity the roles in `inactive_staff_roles` are removed. 0 disables this.
+    #[serde(default)]
+    pub inactive_staff_days: u32,
 
     /// Where logs are sent on Discord, requires `discord` feature and module to be enabled.
     #[serde(default)]
@@ -146,15 +239,29 @@ impl Default for Config {
         Self {
             database_url: default_database_url(),
             database_pool_size: default_database_pool_size(),
+            db_required: default_db_required(),
+            admin_fetch_cache_secs: default_admin_fetch_cache_secs(),
             roles: default_roles(),
             super_admins: default_super_admins(),
             script_sign_key: default_script_sign_key(),
             whitelist: false,
+            trusted_accounts: Vec::new(),
             vc_requires_discord_link: false,
+            auto_role_on_link: String::new(),
+            enforce_uident_ban_evasion: false,
             disallow_room_names: false,
             check_usernames: default_check_usernames(),
             record_player_counts: false,
             player_count_retention_days: 0,
+            username_history_limit: default_username_history_limit(),
+            create_users_on_login: false,
+            welcome_grace_days: default_welcome_grace_days(),
+            welcome_message: String::new(),
+            welcome_discord_alert: false,
+            reply_cooldown_ms: default_reply_cooldown_ms(),
+            daily_action_quotas: ActionQuotas::default(),
+            inactive_staff_roles: Vec::new(),
+            inactive_staff_days: 0,
             mod_log_channel: Default::default(),
             punishment_reasons: PunishReasons::default(),
         }
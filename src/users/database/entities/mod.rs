@@ -5,7 +5,9 @@ pub mod prelude;
 pub mod audit_log;
 pub mod blacklisted_author;
 pub mod blacklisted_level;
+pub mod linked_account;
 pub mod player_count_log;
 pub mod punishment;
 pub mod uident;
 pub mod user;
+pub mod username_history;
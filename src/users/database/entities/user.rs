@@ -20,6 +20,10 @@ pub struct Model {
     pub active_ban: Option<i64>,
     pub active_room_ban: Option<i64>,
     pub discord_id: Option<i64>,
+    pub note: Option<String>,
+    pub last_seen: Option<i64>,
+    pub first_seen: Option<i64>,
+    pub whitelisted_by_discord: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -88,6 +88,12 @@ pub enum LogAction<'a> {
     EditPassword {
         account_id: i32,
     },
+
+    CloseRoom {
+        room_id: u32,
+        room_name: &'a str,
+        owner: i32,
+    },
 }
 
 impl LogAction<'_> {
@@ -110,6 +116,7 @@ impl LogAction<'_> {
             LogAction::EditRoles { .. } => "editroles",
             LogAction::SetRoles { .. } => "setroles",
             LogAction::EditPassword { .. } => "editpassword",
+            LogAction::CloseRoom { .. } => "closeroom",
         }
     }
 
@@ -130,6 +137,7 @@ impl LogAction<'_> {
             LogAction::EditRoles { account_id, .. } => *account_id,
             LogAction::SetRoles { account_id, .. } => *account_id,
             LogAction::EditPassword { account_id, .. } => *account_id,
+            LogAction::CloseRoom { owner, .. } => *owner,
             _ => 0,
         }
     }
@@ -6,6 +6,12 @@ mod m20250910_214142_add_discord_id;
 mod m20251102_125351_add_blacklisted_levels;
 mod m20260326_172649_add_player_counts;
 mod m20260509_135918_add_uident_whitelist;
+mod m20261014_120000_add_user_note;
+mod m20261014_120100_add_username_history;
+mod m20261014_120200_add_user_last_seen;
+mod m20261014_120300_add_linked_accounts;
+mod m20261014_120400_add_user_first_seen;
+mod m20261014_120500_add_whitelisted_by_discord;
 
 pub struct Migrator;
 
@@ -19,6 +25,12 @@ impl MigratorTrait for Migrator {
             Box::new(m20251102_125351_add_blacklisted_levels::Migration),
             Box::new(m20260326_172649_add_player_counts::Migration),
             Box::new(m20260509_135918_add_uident_whitelist::Migration),
+            Box::new(m20261014_120000_add_user_note::Migration),
+            Box::new(m20261014_120100_add_username_history::Migration),
+            Box::new(m20261014_120200_add_user_last_seen::Migration),
+            Box::new(m20261014_120300_add_linked_accounts::Migration),
+            Box::new(m20261014_120400_add_user_first_seen::Migration),
+            Box::new(m20261014_120500_add_whitelisted_by_discord::Migration),
         ]
     }
 }
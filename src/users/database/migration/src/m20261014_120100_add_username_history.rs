@@ -0,0 +1,50 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UsernameHistory::Table)
+                    .col(pk_auto(UsernameHistory::Id))
+                    .col(integer(UsernameHistory::AccountId))
+                    .col(string(UsernameHistory::Username))
+                    .col(big_integer(UsernameHistory::ChangedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_username_history_account_id")
+                    .table(UsernameHistory::Table)
+                    .col(UsernameHistory::AccountId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let mut td = Table::drop();
+        td.table(UsernameHistory::Table);
+        manager.drop_table(td).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum UsernameHistory {
+    Table,
+    Id,
+    AccountId,
+    Username,
+    ChangedAt,
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LinkedAccount::Table)
+                    .col(integer(LinkedAccount::AccountId).primary_key())
+                    .col(integer(LinkedAccount::GroupId))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_linked_account_group_id")
+                    .table(LinkedAccount::Table)
+                    .col(LinkedAccount::GroupId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let mut td = Table::drop();
+        td.table(LinkedAccount::Table);
+        manager.drop_table(td).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum LinkedAccount {
+    Table,
+    AccountId,
+    GroupId,
+}
@@ -1,8 +1,13 @@
+use arc_swap::ArcSwap;
 use server_shared::MultiColor;
 use smallvec::SmallVec;
 use std::{
     fmt::Display,
     num::{NonZeroI64, NonZeroU64},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
@@ -11,7 +16,7 @@ use tracing::warn;
 use sea_orm::{
     ActiveModelTrait,
     ActiveValue::{NotSet, Set},
-    ColumnTrait, ConnectOptions, Database, DatabaseConnection, EntityTrait, ExprTrait,
+    ColumnTrait, Condition, ConnectOptions, Database, DatabaseConnection, EntityTrait, ExprTrait,
     IntoActiveModel, QueryFilter, QueryOrder, QuerySelect,
     prelude::*,
     raw_sql,
@@ -21,6 +26,7 @@ use sea_orm_migration::MigratorTrait;
 mod log_action;
 pub use audit_log::Model as AuditLogModel;
 pub use log_action::LogAction;
+pub use username_history::Model as UsernameHistoryModel;
 
 #[allow(warnings)]
 mod entities;
@@ -69,7 +75,8 @@ pub type DatabaseResult<T> = Result<T, DatabaseError>;
 
 pub struct UsersDb {
     // slightly misleading name but this is a connection pool, not a single connection
-    conn: DatabaseConnection,
+    conn: ArcSwap<DatabaseConnection>,
+    connected: AtomicBool,
 }
 
 fn timestamp() -> NonZeroI64 {
@@ -79,21 +86,54 @@ fn timestamp() -> NonZeroI64 {
 
 impl UsersDb {
     pub async fn new(url: &str, pool_size: u32) -> DatabaseResult<Self> {
+        let db = Self::disconnected();
+        db.conn.store(Arc::new(Self::open(url, pool_size).await?));
+        db.connected.store(true, Ordering::Relaxed);
+
+        Ok(db)
+    }
+
+    /// Creates a database that is not connected to anything, every query on it fails
+    pub fn disconnected() -> Self {
+        Self {
+            conn: ArcSwap::new(Arc::new(DatabaseConnection::default())),
+            connected: AtomicBool::new(false),
+        }
+    }
+
+    /// Connects to the database and runs the migrations, replacing the current connection
+    pub async fn reconnect(&self, url: &str, pool_size: u32) -> DatabaseResult<()> {
+        let conn = Self::open(url, pool_size).await?;
+        Migrator::up(&conn, None).await?;
+
+        self.conn.store(Arc::new(conn));
+        self.connected.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    async fn open(url: &str, pool_size: u32) -> DatabaseResult<DatabaseConnection> {
         let mut opt = ConnectOptions::new(url);
         opt.max_connections(pool_size).min_connections(1);
 
-        let db = Database::connect(opt).await?;
+        Ok(Database::connect(opt).await?)
+    }
 
-        Ok(Self { conn: db })
+    fn conn(&self) -> Arc<DatabaseConnection> {
+        self.conn.load_full()
     }
 
     pub async fn run_migrations(&self) -> DatabaseResult<()> {
-        Migrator::up(&self.conn, None).await?;
+        Migrator::up(&*self.conn(), None).await?;
         Ok(())
     }
 
     pub async fn get_user(&self, account_id: i32) -> DatabaseResult<Option<DbUser>> {
-        let user = User::find_by_id(account_id).one(&self.conn).await?;
+        let user = User::find_by_id(account_id).one(&*self.conn()).await?;
 
         let Some(model) = user else {
             return Ok(None);
@@ -105,7 +145,7 @@ impl UsersDb {
     pub async fn get_linked_discord(&self, account_id: i32) -> DatabaseResult<Option<u64>> {
         let user = User::find_by_id(account_id)
             .into_partial_model::<PartialDiscordUser>()
-            .one(&self.conn)
+            .one(&*self.conn())
             .await?;
 
         Ok(user.and_then(|x| match x.discord_id {
@@ -119,7 +159,7 @@ impl UsersDb {
         discord_id: u64,
     ) -> DatabaseResult<Option<DbUser>> {
         let user =
-            User::find().filter(user::Column::DiscordId.eq(discord_id)).one(&self.conn).await?;
+            User::find().filter(user::Column::DiscordId.eq(discord_id)).one(&*self.conn()).await?;
 
         let Some(model) = user else {
             return Ok(None);
@@ -132,7 +172,7 @@ impl UsersDb {
         let res = User::update_many()
             .filter(user::Column::DiscordId.eq(discord_id as i64))
             .col_expr(user::Column::DiscordId, Expr::value(None::<i64>))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         if res.rows_affected == 0 {
@@ -146,7 +186,7 @@ impl UsersDb {
         let res = User::update_many()
             .filter(user::Column::AccountId.eq(account_id))
             .col_expr(user::Column::DiscordId, Expr::value(None::<i64>))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         if res.rows_affected == 0 {
@@ -164,7 +204,7 @@ impl UsersDb {
         // check if this discord id is already linked to another account
         let existing = User::find()
             .filter(user::Column::DiscordId.eq(discord_id as i64))
-            .one(&self.conn)
+            .one(&*self.conn())
             .await?;
 
         if let Some(existing) = existing {
@@ -179,7 +219,7 @@ impl UsersDb {
         let res = User::update_many()
             .filter(user::Column::AccountId.eq(account_id))
             .col_expr(user::Column::DiscordId, Expr::value(discord_id as i64))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         if res.rows_affected == 0 {
@@ -194,7 +234,7 @@ impl UsersDb {
         let users = User::find()
             .filter(user::Column::DiscordId.is_not_null())
             .filter(user::Column::Roles.is_not_null().and(user::Column::Roles.ne("")))
-            .all(&self.conn)
+            .all(&*self.conn())
             .await?;
 
         let mut out = Vec::with_capacity(users.len());
@@ -210,18 +250,20 @@ impl UsersDb {
 
         let mut user = None;
         if let Ok(id) = query.parse::<i32>() {
-            user = User::find_by_id(id).one(&self.conn).await?;
+            user = User::find_by_id(id).one(&*self.conn()).await?;
         };
 
         // if that didn't work, try exact username match
         if user.is_none() {
-            user = User::find().filter(user::Column::Username.eq(query)).one(&self.conn).await?;
+            user = User::find().filter(user::Column::Username.eq(query)).one(&*self.conn()).await?;
         }
 
         // if that didn't work either, try a contains match
         if user.is_none() {
-            user =
-                User::find().filter(user::Column::Username.contains(query)).one(&self.conn).await?;
+            user = User::find()
+                .filter(user::Column::Username.contains(query))
+                .one(&*self.conn())
+                .await?;
         }
 
         match user {
@@ -240,7 +282,7 @@ impl UsersDb {
         // similar logic as query_user
 
         if let Ok(id) = query.parse::<i32>() {
-            if let Some(user) = User::find_by_id(id).one(&self.conn).await? {
+            if let Some(user) = User::find_by_id(id).one(&*self.conn()).await? {
                 out.push(self.post_user_fetch(user).await?);
             }
         };
@@ -248,7 +290,7 @@ impl UsersDb {
         let users = User::find()
             .filter(user::Column::Username.contains(query))
             .limit(limit as u64)
-            .all(&self.conn)
+            .all(&*self.conn())
             .await?;
 
         for user in users {
@@ -260,7 +302,7 @@ impl UsersDb {
 
     pub async fn query_user_with_role(&self, role_id: &str) -> DatabaseResult<Vec<DbUser>> {
         let users =
-            User::find().filter(user::Column::Roles.contains(role_id)).all(&self.conn).await?;
+            User::find().filter(user::Column::Roles.contains(role_id)).all(&*self.conn()).await?;
 
         let mut out = Vec::new();
         for user in users {
@@ -296,6 +338,10 @@ impl UsersDb {
             active_ban: None,
             active_room_ban: None,
             discord_id: model.discord_id.and_then(|x| NonZeroU64::new(x as u64)),
+            note: model.note.clone().filter(|x| !x.is_empty()),
+            last_seen: model.last_seen.and_then(NonZeroI64::new),
+            first_seen: model.first_seen.and_then(NonZeroI64::new),
+            whitelisted_by_discord: model.whitelisted_by_discord,
         };
 
         if let Some(id) = model.active_mute {
@@ -317,14 +363,14 @@ impl UsersDb {
             active.active_ban = Set(user.active_ban.as_ref().map(|x| x.id));
             active.active_room_ban = Set(user.active_room_ban.as_ref().map(|x| x.id));
 
-            active.update(&self.conn).await?;
+            active.update(&*self.conn()).await?;
         }
 
         Ok(user)
     }
 
     pub async fn get_punishment(&self, id: i64) -> DatabaseResult<Option<UserPunishment>> {
-        let punishment = Punishment::find_by_id(id).one(&self.conn).await?;
+        let punishment = Punishment::find_by_id(id).one(&*self.conn()).await?;
 
         Ok(match punishment {
             None => None,
@@ -349,7 +395,7 @@ impl UsersDb {
         let res = User::update_many()
             .filter(user::Column::AccountId.eq(account_id))
             .col_expr(user::Column::Username, Expr::value(new_username))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         if res.rows_affected == 0 {
@@ -359,12 +405,60 @@ impl UsersDb {
         Ok(())
     }
 
+    /// Records a previous username of the user, keeping at most `limit` newest entries
+    pub async fn add_username_history(
+        &self,
+        account_id: i32,
+        old_username: &str,
+        limit: u32,
+    ) -> DatabaseResult<()> {
+        username_history::ActiveModel {
+            account_id: Set(account_id as i64),
+            username: Set(old_username.to_owned()),
+            changed_at: Set(timestamp().get()),
+            ..Default::default()
+        }
+        .insert(&*self.conn())
+        .await?;
+
+        let stale: Vec<i64> = UsernameHistory::find()
+            .select_only()
+            .column(username_history::Column::Id)
+            .filter(username_history::Column::AccountId.eq(account_id))
+            .order_by_desc(username_history::Column::Id)
+            .offset(limit as u64)
+            .into_tuple()
+            .all(&*self.conn())
+            .await?;
+
+        if !stale.is_empty() {
+            UsernameHistory::delete_many()
+                .filter(username_history::Column::Id.is_in(stale))
+                .exec(&*self.conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns previous usernames of the user, newest first
+    pub async fn get_username_history(
+        &self,
+        account_id: i32,
+    ) -> DatabaseResult<Vec<username_history::Model>> {
+        Ok(UsernameHistory::find()
+            .filter(username_history::Column::AccountId.eq(account_id))
+            .order_by_desc(username_history::Column::Id)
+            .all(&*self.conn())
+            .await?)
+    }
+
     /// Inserts a new uident association, returns 'false' if the uident is already associated with the account ID
     pub async fn insert_uident(&self, account_id: i32, ident: &str) -> DatabaseResult<bool> {
         let existing = Uident::find()
             .filter(uident::Column::AccountId.eq(account_id))
             .filter(uident::Column::Ident.eq(ident))
-            .count(&self.conn)
+            .count(&*self.conn())
             .await?
             > 0;
 
@@ -378,7 +472,7 @@ impl UsersDb {
             ..Default::default()
         };
 
-        model.insert(&self.conn).await?;
+        model.insert(&*self.conn()).await?;
 
         Ok(true)
     }
@@ -395,7 +489,7 @@ impl UsersDb {
             query = query.filter(uident::Column::Whitelisted.eq(false));
         }
 
-        Ok(query.count(&self.conn).await?)
+        Ok(query.count(&*self.conn()).await?)
     }
 
     pub async fn any_active_punishments_for_uident(&self, ident: &str) -> DatabaseResult<bool> {
@@ -404,7 +498,7 @@ impl UsersDb {
         let users = User::find().from_raw_sql(raw_sql!(
             Sqlite,
             r#"select u.* from uident ui right join user u on u.account_id = ui.account_id where ident = {ident}"#
-        )).all(&self.conn).await?;
+        )).all(&*self.conn()).await?;
 
         for user in users {
             let try_one = async |id| -> bool {
@@ -433,7 +527,7 @@ impl UsersDb {
         let models = Uident::find()
             .filter(uident::Column::Ident.eq(ident))
             .into_partial_model::<PartialAccountUident>()
-            .all(&self.conn)
+            .all(&*self.conn())
             .await?;
 
         let mut out = SmallVec::new();
@@ -446,15 +540,19 @@ impl UsersDb {
     }
 
     pub async fn get_user_uident(&self, account_id: i32) -> DatabaseResult<Option<String>> {
-        let uident =
-            Uident::find().filter(uident::Column::AccountId.eq(account_id)).one(&self.conn).await?;
+        let uident = Uident::find()
+            .filter(uident::Column::AccountId.eq(account_id))
+            .one(&*self.conn())
+            .await?;
 
         Ok(uident.map(|x| x.ident))
     }
 
     pub async fn get_user_uidents(&self, account_id: i32) -> DatabaseResult<Vec<String>> {
-        let uidents =
-            Uident::find().filter(uident::Column::AccountId.eq(account_id)).all(&self.conn).await?;
+        let uidents = Uident::find()
+            .filter(uident::Column::AccountId.eq(account_id))
+            .all(&*self.conn())
+            .await?;
 
         Ok(uidents.into_iter().map(|x| x.ident).collect())
     }
@@ -463,12 +561,80 @@ impl UsersDb {
         Uident::update_many()
             .filter(uident::Column::Ident.eq(ident))
             .col_expr(uident::Column::Whitelisted, Expr::value(true))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         Ok(())
     }
 
+    /// Marks the accounts as belonging to the same person, merging any groups they were already in
+    pub async fn link_accounts(&self, account_ids: &[i32]) -> DatabaseResult<()> {
+        let mut members: Vec<i64> = account_ids.iter().map(|&x| x as i64).collect();
+
+        let groups: Vec<i64> = LinkedAccount::find()
+            .filter(linked_account::Column::AccountId.is_in(members.clone()))
+            .all(&*self.conn())
+            .await?
+            .into_iter()
+            .map(|m| m.group_id)
+            .collect();
+
+        if !groups.is_empty() {
+            let grouped = LinkedAccount::find()
+                .filter(linked_account::Column::GroupId.is_in(groups))
+                .all(&*self.conn())
+                .await?;
+
+            members.extend(grouped.into_iter().map(|m| m.account_id));
+        }
+
+        members.sort_unstable();
+        members.dedup();
+
+        // the group is identified by its lowest account ID
+        let Some(&group_id) = members.first() else {
+            return Ok(());
+        };
+
+        LinkedAccount::delete_many()
+            .filter(linked_account::Column::AccountId.is_in(members.clone()))
+            .exec(&*self.conn())
+            .await?;
+
+        LinkedAccount::insert_many(members.into_iter().map(|account_id| {
+            linked_account::ActiveModel {
+                account_id: Set(account_id),
+                group_id: Set(group_id),
+            }
+        }))
+        .exec(&*self.conn())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the account from its linked group, returns `false` if it was not linked
+    pub async fn unlink_account(&self, account_id: i32) -> DatabaseResult<bool> {
+        let result = LinkedAccount::delete_by_id(account_id as i64).exec(&*self.conn()).await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Returns all accounts linked with this one (including itself), or an empty vec if not linked
+    pub async fn get_linked_accounts(&self, account_id: i32) -> DatabaseResult<Vec<i32>> {
+        let Some(model) = LinkedAccount::find_by_id(account_id as i64).one(&*self.conn()).await?
+        else {
+            return Ok(Vec::new());
+        };
+
+        Ok(LinkedAccount::find()
+            .filter(linked_account::Column::GroupId.eq(model.group_id))
+            .all(&*self.conn())
+            .await?
+            .into_iter()
+            .map(|m| m.account_id as i32)
+            .collect())
+    }
+
     pub async fn update_user(
         &self,
         account_id: i32,
@@ -484,7 +650,7 @@ impl UsersDb {
             .col_expr(user::Column::Color1, Expr::value(color1))
             .col_expr(user::Column::Color2, Expr::value(color2))
             .col_expr(user::Column::GlowColor, Expr::value(glow_color))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         if result.rows_affected == 0 {
@@ -501,17 +667,82 @@ impl UsersDb {
                 ..Default::default()
             };
 
-            new_user.insert(&self.conn).await?;
+            new_user.insert(&*self.conn()).await?;
         }
 
         Ok(())
     }
 
+    /// Inserts an entry for a user that does not have one yet, stamped with `first_seen`.
+    /// Returns `false` and leaves the table untouched if the user already exists.
+    pub async fn insert_first_login(
+        &self,
+        account_id: i32,
+        username: &str,
+        cube: i16,
+        color1: u16,
+        color2: u16,
+        glow_color: u16,
+    ) -> DatabaseResult<bool> {
+        if User::find_by_id(account_id).one(&*self.conn()).await?.is_some() {
+            return Ok(false);
+        }
+
+        let new_user = user::ActiveModel {
+            account_id: Set(account_id as i64),
+            username: Set(Some(username.to_owned())),
+            is_whitelisted: Set(false),
+            cube: Set(cube as i64),
+            color1: Set(color1 as i64),
+            color2: Set(color2 as i64),
+            glow_color: Set(glow_color as i64),
+            first_seen: Set(Some(timestamp().get())),
+            ..Default::default()
+        };
+
+        new_user.insert(&*self.conn()).await?;
+        Ok(true)
+    }
+
+    /// Returns the earliest `first_seen` of any user, i.e. when entries started being created on login
+    pub async fn oldest_first_seen(&self) -> DatabaseResult<Option<i64>> {
+        let oldest: Option<Option<i64>> = User::find()
+            .select_only()
+            .column(user::Column::FirstSeen)
+            .filter(user::Column::FirstSeen.is_not_null())
+            .order_by_asc(user::Column::FirstSeen)
+            .into_tuple()
+            .one(&*self.conn())
+            .await?;
+
+        Ok(oldest.flatten())
+    }
+
+    /// Sets the whitelist status manually, this clears the Discord grant so that it's not revoked automatically
     pub async fn set_whitelisted(&self, account_id: i32, whitelisted: bool) -> DatabaseResult<()> {
+        self.set_whitelisted_inner(account_id, whitelisted, false).await
+    }
+
+    /// Sets the whitelist status as granted (or revoked) by Discord membership
+    pub async fn set_discord_whitelisted(
+        &self,
+        account_id: i32,
+        whitelisted: bool,
+    ) -> DatabaseResult<()> {
+        self.set_whitelisted_inner(account_id, whitelisted, whitelisted).await
+    }
+
+    async fn set_whitelisted_inner(
+        &self,
+        account_id: i32,
+        whitelisted: bool,
+        by_discord: bool,
+    ) -> DatabaseResult<()> {
         let result = User::update_many()
             .filter(user::Column::AccountId.eq(account_id))
             .col_expr(user::Column::IsWhitelisted, Expr::value(whitelisted))
-            .exec(&self.conn)
+            .col_expr(user::Column::WhitelistedByDiscord, Expr::value(by_discord))
+            .exec(&*self.conn())
             .await?;
 
         if result.rows_affected == 0 {
@@ -521,13 +752,60 @@ impl UsersDb {
         Ok(())
     }
 
+    /// Sets the moderator note for the given user, an empty note removes it
+    pub async fn set_note(&self, account_id: i32, note: &str) -> DatabaseResult<()> {
+        let result = User::update_many()
+            .filter(user::Column::AccountId.eq(account_id))
+            .col_expr(user::Column::Note, Expr::value((!note.is_empty()).then_some(note)))
+            .exec(&*self.conn())
+            .await?;
+
+        if result.rows_affected == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    pub async fn update_last_seen(&self, account_ids: &[i32]) -> DatabaseResult<()> {
+        let now = timestamp().get();
+
+        // keep the amount of bound parameters reasonable for sqlite
+        for chunk in account_ids.chunks(500) {
+            User::update_many()
+                .filter(user::Column::AccountId.is_in(chunk.iter().copied()))
+                .col_expr(user::Column::LastSeen, Expr::value(now))
+                .exec(&*self.conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn fetch_all_with_roles(&self) -> DatabaseResult<Vec<user::Model>> {
         Ok(User::find()
             .filter(user::Column::Roles.is_not_null().and(user::Column::Roles.ne("")))
-            .all(&self.conn)
+            .all(&*self.conn())
             .await?)
     }
 
+    /// Fetches users whose role string contains any of the given role IDs. This can return extra users
+    /// whose roles merely contain one of the IDs as a substring, so callers should check the roles again.
+    pub async fn fetch_all_with_any_role(
+        &self,
+        role_ids: &[String],
+    ) -> DatabaseResult<Vec<user::Model>> {
+        if role_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let cond = role_ids
+            .iter()
+            .fold(Condition::any(), |cond, id| cond.add(user::Column::Roles.contains(id)));
+
+        Ok(User::find().filter(cond).all(&*self.conn()).await?)
+    }
+
     /// Returns whether the user was modified
     fn expire_punishments(&self, user: &mut DbUser) -> bool {
         let mut modified = false;
@@ -549,7 +827,7 @@ impl UsersDb {
     }
 
     pub async fn get_admin_password_hash(&self, account_id: i32) -> DatabaseResult<Option<String>> {
-        let user = User::find_by_id(account_id).one(&self.conn).await?;
+        let user = User::find_by_id(account_id).one(&*self.conn()).await?;
 
         Ok(user.and_then(|u| u.admin_password_hash))
     }
@@ -558,7 +836,7 @@ impl UsersDb {
         User::update_many()
             .filter(user::Column::AccountId.eq(account_id))
             .col_expr(user::Column::AdminPasswordHash, Expr::value(hash))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         Ok(())
@@ -567,7 +845,7 @@ impl UsersDb {
     pub async fn get_punishment_count(&self, account_id: i32) -> DatabaseResult<u32> {
         let count = Punishment::find()
             .filter(punishment::Column::AccountId.eq(account_id))
-            .count(&self.conn)
+            .count(&*self.conn())
             .await?;
 
         Ok(count as u32)
@@ -646,9 +924,9 @@ impl UsersDb {
         };
 
         let pun_id = if updating {
-            pun.update(&self.conn).await?.id
+            pun.update(&*self.conn()).await?.id
         } else {
-            pun.insert(&self.conn).await?.id
+            pun.insert(&*self.conn()).await?.id
         };
 
         // update active mute / ban / room ban
@@ -689,7 +967,7 @@ impl UsersDb {
             }
         };
 
-        let result = stmt.exec(&self.conn).await?;
+        let result = stmt.exec(&*self.conn()).await?;
 
         Ok(result.rows_affected > 0)
     }
@@ -698,7 +976,7 @@ impl UsersDb {
         let res = User::update_many()
             .filter(user::Column::AccountId.eq(account_id))
             .col_expr(user::Column::Roles, Expr::value(roles))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         Ok(res.rows_affected > 0)
@@ -738,11 +1016,35 @@ impl UsersDb {
 
         stmt = stmt.order_by_desc(audit_log::Column::Id).limit(page_size).offset(page * page_size);
 
-        let results: Vec<audit_log::Model> = stmt.all(&self.conn).await?;
+        let results: Vec<audit_log::Model> = stmt.all(&*self.conn()).await?;
 
         Ok(results)
     }
 
+    /// Returns the distinct accounts targeted by the issuer with the given action type,
+    /// within the given time window (`after` inclusive, `before` exclusive)
+    pub async fn fetch_action_targets(
+        &self,
+        issuer: i32,
+        r#type: &str,
+        after: i64,
+        before: i64,
+    ) -> DatabaseResult<Vec<i32>> {
+        let targets: Vec<Option<i64>> = AuditLog::find()
+            .select_only()
+            .column(audit_log::Column::TargetAccountId)
+            .distinct()
+            .filter(audit_log::Column::AccountId.eq(issuer))
+            .filter(audit_log::Column::Type.eq(r#type))
+            .filter(audit_log::Column::Timestamp.gte(after))
+            .filter(audit_log::Column::Timestamp.lt(before))
+            .into_tuple()
+            .all(&*self.conn())
+            .await?;
+
+        Ok(targets.into_iter().flatten().map(|id| id as i32).collect())
+    }
+
     pub async fn truncate_unimportant_logs(&self, cutoff: Duration) -> DatabaseResult<()> {
         let cutoff = timestamp().get() - cutoff.as_secs() as i64;
 
@@ -750,7 +1052,7 @@ impl UsersDb {
             .filter(
                 audit_log::Column::Timestamp.lt(cutoff).and(audit_log::Column::Type.eq("setroles")),
             )
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         Ok(())
@@ -789,7 +1091,7 @@ impl UsersDb {
             );
         }
 
-        let count = stmt.count(&self.conn).await?;
+        let count = stmt.count(&*self.conn()).await?;
 
         Ok(count as u32)
     }
@@ -850,6 +1152,10 @@ impl UsersDb {
                 // no extra fields
             }
 
+            LogAction::CloseRoom { room_id, room_name, .. } => {
+                entry.message = Set(Some(format!("{room_name} ({room_id})")));
+            }
+
             LogAction::NoticeEveryone { .. }
             | LogAction::NoticeGroup { .. }
             | LogAction::NoticeReply { .. } => {
@@ -857,14 +1163,14 @@ impl UsersDb {
             }
         }
 
-        entry.insert(&self.conn).await?;
+        entry.insert(&*self.conn()).await?;
 
         Ok(())
     }
 
     pub async fn fetch_blacklisted_levels(&self) -> DatabaseResult<Vec<i32>> {
         Ok(BlacklistedLevel::find()
-            .all(&self.conn)
+            .all(&*self.conn())
             .await?
             .into_iter()
             .map(|x| x.id as i32)
@@ -873,7 +1179,7 @@ impl UsersDb {
 
     pub async fn fetch_blacklisted_authors(&self) -> DatabaseResult<Vec<i32>> {
         Ok(BlacklistedAuthor::find()
-            .all(&self.conn)
+            .all(&*self.conn())
             .await?
             .into_iter()
             .map(|x| x.id as i32)
@@ -886,7 +1192,7 @@ impl UsersDb {
             timestamp: Set(timestamp().get()),
         };
 
-        entry.insert(&self.conn).await?;
+        entry.insert(&*self.conn()).await?;
 
         Ok(())
     }
@@ -896,7 +1202,7 @@ impl UsersDb {
 
         PlayerCountLog::delete_many()
             .filter(player_count_log::Column::Timestamp.lt(cutoff))
-            .exec(&self.conn)
+            .exec(&*self.conn())
             .await?;
 
         Ok(())
@@ -911,7 +1217,7 @@ impl UsersDb {
         let counts = PlayerCountLog::find()
             .filter(player_count_log::Column::Timestamp.gte(cutoff))
             .order_by_asc(player_count_log::Column::Timestamp)
-            .all(&self.conn)
+            .all(&*self.conn())
             .await?;
 
         Ok(counts)
@@ -925,6 +1231,7 @@ pub enum UserPunishmentType {
     RoomBan,
 }
 
+#[derive(Clone)]
 pub struct UserPunishment {
     pub id: i64,
     pub account_id: i32,
@@ -935,6 +1242,7 @@ pub struct UserPunishment {
     pub issued_at: Option<NonZeroI64>,
 }
 
+#[derive(Clone)]
 pub struct DbUser {
     pub account_id: i32,
     pub username: Option<String>,
@@ -946,6 +1254,11 @@ pub struct DbUser {
     pub active_ban: Option<UserPunishment>,
     pub active_room_ban: Option<UserPunishment>,
     pub discord_id: Option<NonZeroU64>,
+    pub note: Option<String>,
+    pub last_seen: Option<NonZeroI64>,
+    pub first_seen: Option<NonZeroI64>,
+    /// Whether the whitelist was granted automatically for being in the Discord server
+    pub whitelisted_by_discord: bool,
 }
 
 impl UserPunishment {
@@ -4,7 +4,7 @@ use std::{
     fmt::Write,
     num::NonZeroI64,
     sync::{Arc, OnceLock},
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 #[cfg(feature = "discord")]
@@ -16,6 +16,9 @@ use {
     poise::serenity_prelude::{CreateEmbed, CreateEmbedAuthor, Member},
 };
 
+#[cfg(feature = "analytics")]
+use crate::analytics::{AnalyticsModule, ModerationEvent};
+
 #[cfg(feature = "web")]
 use {
     crate::web::{WebModule, WebState},
@@ -32,16 +35,16 @@ use crate::{
         handler::{ClientStateHandle, ConnectionHandler},
         module::{ConfigurableModule, ModuleInitResult, ServerModule},
     },
-    users::database::{AuditLogModel, LogAction},
+    users::database::{AuditLogModel, LogAction, UsernameHistoryModel},
 };
 
 use arc_swap::ArcSwap;
 #[cfg(feature = "web")]
 use axum::{extract::Path, http::StatusCode};
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 use server_shared::{
     MultiColor,
-    data::SrvUserData,
+    data::{PlayerIconData, SrvUserData},
     qunet::server::{ServerHandle, WeakServerHandle},
 };
 
@@ -68,6 +71,8 @@ pub enum PunishUserError {
     Permissions,
     #[error("Specified punishment is not active")]
     NotPunished,
+    #[error("Daily limit for this action has been reached")]
+    QuotaExceeded,
 }
 
 #[derive(Error, Debug)]
@@ -80,6 +85,8 @@ pub enum Error {
     NotFound,
     #[error("Insufficient permissions")]
     Permissions,
+    #[error("Daily limit for this action has been reached")]
+    QuotaExceeded,
     #[error("Failed to fetch user from GD api: {0}")]
     Fetch(#[from] GDApiFetchError),
 
@@ -88,6 +95,7 @@ pub enum Error {
     Discord(#[from] crate::discord::BotError),
 }
 
+#[derive(serde::Serialize, Clone)]
 pub struct FetchedMod {
     pub account_id: i32,
     pub username: String,
@@ -114,6 +122,8 @@ pub struct ComputedRole {
     pub can_send_features: bool,
     pub can_rate_features: bool,
     pub can_name_rooms: bool,
+    /// Notice reply cooldown override from the strongest role that sets one
+    pub reply_cooldown_ms: Option<u32>,
 }
 
 impl ComputedRole {
@@ -152,6 +162,47 @@ impl LinkedDiscordAccount {
     }
 }
 
+/// Moderator actions that are subject to `daily_action_quotas`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum QuotaAction {
+    Ban,
+    Mute,
+    RoomBan,
+    EditRoles,
+}
+
+impl From<UserPunishmentType> for QuotaAction {
+    fn from(value: UserPunishmentType) -> Self {
+        match value {
+            UserPunishmentType::Ban => Self::Ban,
+            UserPunishmentType::Mute => Self::Mute,
+            UserPunishmentType::RoomBan => Self::RoomBan,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ActionQuotaState {
+    /// Days since the unix epoch, counts are reset once this changes
+    day: u64,
+    counts: FxHashMap<(i32, QuotaAction), u32>,
+    /// Moderators that were already alerted about for going over the quota today
+    alerted: FxHashSet<(i32, QuotaAction)>,
+}
+
+impl ActionQuotaState {
+    /// Resets the counts if the day changed since they were last used
+    fn roll_over(&mut self) {
+        let today = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() / 86400;
+
+        if self.day != today {
+            self.day = today;
+            self.counts.clear();
+            self.alerted.clear();
+        }
+    }
+}
+
 type CachedPlayerCounts = (Instant, Vec<PlayerCountHistoryEntry>);
 
 pub struct UsersModule {
@@ -159,6 +210,8 @@ pub struct UsersModule {
     server: OnceLock<WeakServerHandle<ConnectionHandler>>,
     config: ArcSwap<Config>,
     roles: Vec<Role>, // index = numeric role ID
+    /// String IDs of all roles that grant moderation permissions
+    staff_role_ids: Vec<String>,
     gd_client: GDApiClient,
     #[cfg(feature = "discord")]
     discord: Option<Arc<DiscordModule>>,
@@ -167,8 +220,19 @@ pub struct UsersModule {
 
     blacklisted_authors: ArcSwap<FxHashSet<i32>>,
     blacklisted_levels: ArcSwap<FxHashSet<i32>>,
+    trusted_accounts: ArcSwap<FxHashSet<i32>>,
 
     player_counts_cache: RwLock<HashMap<Duration, CachedPlayerCounts>>,
+    /// Result of `fetch_moderators` along with when it expires, used by the web API
+    moderators_cache: RwLock<Option<(Instant, Vec<FetchedMod>)>>,
+    /// Users that disconnected since the last time `last_seen` was written to the database
+    pending_last_seen: parking_lot::Mutex<FxHashSet<i32>>,
+    /// Recently fetched users for admin read paths, see `admin_get_user`
+    admin_user_cache: parking_lot::Mutex<FxHashMap<i32, (Instant, DbUser)>>,
+    /// Actions performed by each moderator today, see `check_action_quota`
+    action_quotas: parking_lot::Mutex<ActionQuotaState>,
+    /// Earliest `first_seen` in the database, see `create_user_on_login`
+    first_login_tracking_since: parking_lot::Mutex<Option<i64>>,
 }
 
 impl UsersModule {
@@ -176,6 +240,12 @@ impl UsersModule {
         self.config.load()
     }
 
+    /// Returns the notice reply cooldown for a user with the given role
+    pub fn reply_cooldown(&self, role: Option<&ComputedRole>) -> Duration {
+        let ms = role.and_then(|r| r.reply_cooldown_ms).unwrap_or(self.config().reply_cooldown_ms);
+        Duration::from_millis(ms as u64)
+    }
+
     pub fn disallow_room_names(&self) -> bool {
         self.config().disallow_room_names
     }
@@ -184,10 +254,23 @@ impl UsersModule {
         self.config().check_usernames
     }
 
+    pub fn enforce_uident_ban_evasion(&self) -> bool {
+        self.config().enforce_uident_ban_evasion
+    }
+
     pub fn vc_requires_discord(&self) -> bool {
         self.config().vc_requires_discord_link
     }
 
+    pub fn is_super_admin(&self, account_id: i32) -> bool {
+        self.config().super_admins.contains(&account_id)
+    }
+
+    /// Whether the account is exempt from the word filter and message rate limits
+    pub fn is_trusted(&self, account_id: i32) -> bool {
+        self.trusted_accounts.load().contains(&account_id)
+    }
+
     pub fn gather_user_data(&self, client: &ClientStateHandle) -> SrvUserData {
         let is_muted = client.active_mute.lock().is_some();
         let is_linked = client.is_discord_linked();
@@ -206,6 +289,68 @@ impl UsersModule {
         self.db.get_user(account_id).await
     }
 
+    /// Like `get_user`, but the result may come from a short-lived cache.
+    /// Only meant for admin read paths, writes to the user invalidate the cached entry.
+    pub async fn admin_get_user(&self, account_id: i32) -> DatabaseResult<Option<DbUser>> {
+        if let Some(user) = self.get_cached_user(account_id) {
+            return Ok(Some(user));
+        }
+
+        let user = self.db.get_user(account_id).await?;
+
+        if let Some(user) = &user {
+            self.cache_user(user);
+        }
+
+        Ok(user)
+    }
+
+    /// Like `query_user`, but the result may come from a short-lived cache, see `admin_get_user`
+    pub async fn admin_query_user(&self, query: &str) -> DatabaseResult<Option<DbUser>> {
+        if let Ok(id) = query.parse::<i32>()
+            && let Some(user) = self.get_cached_user(id)
+        {
+            return Ok(Some(user));
+        }
+
+        let user = self.db.query_user(query).await?;
+
+        if let Some(user) = &user {
+            self.cache_user(user);
+        }
+
+        Ok(user)
+    }
+
+    fn get_cached_user(&self, account_id: i32) -> Option<DbUser> {
+        let ttl = Duration::from_secs(self.config().admin_fetch_cache_secs);
+
+        self.admin_user_cache
+            .lock()
+            .get(&account_id)
+            .filter(|(at, _)| at.elapsed() < ttl)
+            .map(|(_, user)| user.clone())
+    }
+
+    fn cache_user(&self, user: &DbUser) {
+        let ttl = Duration::from_secs(self.config().admin_fetch_cache_secs);
+        if ttl.is_zero() {
+            return;
+        }
+
+        let mut cache = self.admin_user_cache.lock();
+
+        if cache.len() >= 256 {
+            cache.retain(|_, (at, _)| at.elapsed() < ttl);
+        }
+
+        cache.insert(user.account_id, (Instant::now(), user.clone()));
+    }
+
+    fn invalidate_cached_user(&self, account_id: i32) {
+        self.admin_user_cache.lock().remove(&account_id);
+    }
+
     #[cfg(feature = "discord")]
     pub async fn get_linked_discord(
         &self,
@@ -270,8 +415,11 @@ impl UsersModule {
                 icons.glow_color,
             )
             .await?;
+        self.invalidate_cached_user(account_id);
 
         self.db.link_discord_account(account_id, discord_id).await?;
+        self.invalidate_cached_user(account_id);
+        self.grant_link_role(account_id).await;
         self.server().handler().notify_user_linked(handle).await;
 
         Ok(())
@@ -283,7 +431,67 @@ impl UsersModule {
         account_id: i32,
         discord_id: u64,
     ) -> DatabaseResult<()> {
-        self.db.link_discord_account(account_id, discord_id).await
+        self.db.link_discord_account(account_id, discord_id).await?;
+        self.invalidate_cached_user(account_id);
+        self.grant_link_role(account_id).await;
+
+        Ok(())
+    }
+
+    /// Whitelists a linked user for being a member of the Discord server,
+    /// unless they are already whitelisted
+    #[cfg(feature = "discord")]
+    pub async fn grant_discord_whitelist(&self, user: &DbUser) -> DatabaseResult<()> {
+        if user.is_whitelisted {
+            return Ok(());
+        }
+
+        info!("Whitelisting {} ({}) for Discord membership", user.username(), user.account_id);
+        self.db.set_discord_whitelisted(user.account_id, true).await?;
+        self.invalidate_cached_user(user.account_id);
+
+        Ok(())
+    }
+
+    /// Returns the ID of the role that linked users automatically get, if there is one
+    pub fn link_role_id(&self) -> Option<u8> {
+        let config = self.config();
+        if config.auto_role_on_link.is_empty() {
+            return None;
+        }
+
+        let role = self.get_role_by_str_id(&config.auto_role_on_link).map(|(idx, _)| idx as u8);
+        if role.is_none() {
+            warn!("auto_role_on_link is set to an unknown role '{}'", config.auto_role_on_link);
+        }
+
+        role
+    }
+
+    #[cfg(feature = "discord")]
+    async fn grant_link_role(&self, account_id: i32) {
+        let Some(role_id) = self.link_role_id() else {
+            return;
+        };
+
+        let res: Result<(), Error> = try {
+            let user = self.get_user(account_id).await?.ok_or(Error::NotFound)?;
+            let mut role_ids = self.role_str_to_ids(user.roles.as_deref().unwrap_or(""));
+
+            if !role_ids.contains(&role_id) {
+                role_ids.push(role_id);
+                self.system_set_roles(account_id, &role_ids).await?;
+
+                info!(
+                    "Granted role {} to {account_id} for linking Discord",
+                    self.roles[role_id as usize].id
+                );
+            }
+        };
+
+        if let Err(e) = res {
+            warn!("Failed to grant the Discord link role to {account_id}: {e}");
+        }
     }
 
     #[cfg(feature = "discord")]
@@ -296,6 +504,7 @@ impl UsersModule {
 
     #[cfg(feature = "discord")]
     pub async fn unlink_discord_inverse(&self, discord_id: u64) -> DatabaseResult<()> {
+        self.admin_user_cache.lock().clear();
         self.db.unlink_discord_inverse(discord_id).await
     }
 
@@ -313,6 +522,7 @@ impl UsersModule {
         // otherwise, allow unlinking
 
         self.db.unlink_discord(account_id).await?;
+        self.invalidate_cached_user(account_id);
 
         if let Some(d) = self.discord.as_ref() {
             d.send_alert(DiscordMessage::new().content(format!(
@@ -395,8 +605,28 @@ impl UsersModule {
         Ok(self.db.get_user(user.account_id).await?)
     }
 
-    pub async fn update_username(&self, account_id: i32, new_username: &str) -> DatabaseResult<()> {
-        self.db.update_username(account_id, new_username).await
+    pub async fn update_username(
+        &self,
+        account_id: i32,
+        old_username: &str,
+        new_username: &str,
+    ) -> DatabaseResult<()> {
+        self.db.update_username(account_id, new_username).await?;
+        self.invalidate_cached_user(account_id);
+
+        let limit = self.config().username_history_limit;
+        if limit != 0 && !old_username.is_empty() {
+            self.db.add_username_history(account_id, old_username, limit).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_username_history(
+        &self,
+        account_id: i32,
+    ) -> DatabaseResult<Vec<UsernameHistoryModel>> {
+        self.db.get_username_history(account_id).await
     }
 
     pub async fn insert_uident(&self, account_id: i32, ident: &str) -> DatabaseResult<bool> {
@@ -420,6 +650,22 @@ impl UsersModule {
         }
     }
 
+    /// Returns the active ban of the first account out of `accounts` (excluding `exclude`) that is
+    /// currently banned
+    pub async fn find_banned_account(
+        &self,
+        accounts: &[i32],
+        exclude: i32,
+    ) -> DatabaseResult<Option<UserPunishment>> {
+        for &id in accounts.iter().filter(|&&id| id != exclude) {
+            if let Some(ban) = self.get_user(id).await?.and_then(|u| u.active_ban) {
+                return Ok(Some(ban));
+            }
+        }
+
+        Ok(None)
+    }
+
     pub async fn any_active_punishments_for_uident(&self, ident: &str) -> DatabaseResult<bool> {
         self.db.any_active_punishments_for_uident(ident).await
     }
@@ -436,6 +682,31 @@ impl UsersModule {
         self.db.whitelist_uident(ident).await
     }
 
+    pub async fn link_accounts(&self, issuer_id: i32, account_ids: &[i32]) -> DatabaseResult<()> {
+        info!("{issuer_id} marked accounts as linked: {account_ids:?}");
+        self.db.link_accounts(account_ids).await
+    }
+
+    pub async fn unlink_account(&self, issuer_id: i32, account_id: i32) -> DatabaseResult<bool> {
+        info!("{issuer_id} unlinked account {account_id}");
+        self.db.unlink_account(account_id).await
+    }
+
+    pub async fn get_linked_accounts(&self, account_id: i32) -> DatabaseResult<Vec<i32>> {
+        self.db.get_linked_accounts(account_id).await
+    }
+
+    /// Removes accounts that are known to belong to the same person as `account_id`
+    pub async fn filter_linked_accounts(
+        &self,
+        account_id: i32,
+        accounts: &mut SmallVec<[i32; 8]>,
+    ) -> DatabaseResult<()> {
+        let linked = self.db.get_linked_accounts(account_id).await?;
+        accounts.retain(|id| !linked.contains(id));
+        Ok(())
+    }
+
     pub async fn get_punishment_count(&self, account_id: i32) -> DatabaseResult<u32> {
         self.db.get_punishment_count(account_id).await
     }
@@ -501,6 +772,7 @@ impl UsersModule {
         let mut can_send_features = None;
         let mut can_rate_features = None;
         let mut can_name_rooms = None;
+        let mut reply_cooldown_ms = None;
 
         let iter = iter.filter_map(|id| self.get_role(id).map(|role| (id, role)));
 
@@ -528,6 +800,10 @@ impl UsersModule {
             apply_permission(&mut can_rate_features, role.can_rate_features);
             apply_permission(&mut can_name_rooms, role.can_name_rooms);
 
+            if role.reply_cooldown_ms.is_some() && (reply_cooldown_ms.is_none() || !is_weaker) {
+                reply_cooldown_ms = role.reply_cooldown_ms;
+            }
+
             let _ = out_role.roles.push(role_id);
 
             if !is_weaker {
@@ -558,6 +834,7 @@ impl UsersModule {
         out_role.can_send_features = can_send_features.unwrap_or(default);
         out_role.can_rate_features = can_rate_features.unwrap_or(default);
         out_role.can_name_rooms = can_name_rooms.unwrap_or(default);
+        out_role.reply_cooldown_ms = reply_cooldown_ms.or(default.then_some(0));
 
         // sort roles by priority descending
         out_role.roles.sort_unstable_by_key(|&id| {
@@ -613,6 +890,70 @@ impl UsersModule {
         Ok(hash.map(|hash| pwhash::verify(password, &hash)).unwrap_or(false))
     }
 
+    /// Returns the daily limit for the action, or `None` if the issuer is not limited
+    fn action_quota_limit(&self, issuer_id: i32, action: QuotaAction) -> Option<u32> {
+        if issuer_id == 0 || self.is_super_admin(issuer_id) {
+            return None;
+        }
+
+        let config = self.config();
+        let quotas = &config.daily_action_quotas;
+        let limit = match action {
+            QuotaAction::Ban => quotas.bans,
+            QuotaAction::Mute => quotas.mutes,
+            QuotaAction::RoomBan => quotas.room_bans,
+            QuotaAction::EditRoles => quotas.role_edits,
+        };
+
+        (limit != 0).then_some(limit)
+    }
+
+    /// Returns `false` if the moderator already used up their daily quota for this action,
+    /// in which case the action should be refused. This does not count the action itself,
+    /// call `record_quota_action` once it succeeds.
+    fn check_action_quota(&self, issuer_id: i32, action: QuotaAction) -> bool {
+        let Some(limit) = self.action_quota_limit(issuer_id, action) else {
+            return true;
+        };
+
+        let first_refusal = {
+            let mut state = self.action_quotas.lock();
+            state.roll_over();
+
+            if state.counts.get(&(issuer_id, action)).copied().unwrap_or(0) < limit {
+                return true;
+            }
+
+            // only alert once per day, on the first refused attempt
+            state.alerted.insert((issuer_id, action))
+        };
+
+        warn!("{issuer_id} exceeded the daily quota for {action:?} ({limit}), refusing the action");
+
+        #[cfg(feature = "discord")]
+        if first_refusal && let Some(d) = self.discord.as_ref() {
+            d.send_alert(DiscordMessage::new().content(format!(
+                ":warning: Moderator {issuer_id} reached the daily limit of {limit} for {action:?}, further attempts today are refused. This may indicate a compromised account."
+            )));
+        }
+
+        #[cfg(not(feature = "discord"))]
+        let _ = first_refusal;
+
+        false
+    }
+
+    /// Counts a successful action towards the daily quota of the moderator
+    fn record_quota_action(&self, issuer_id: i32, action: QuotaAction) {
+        if self.action_quota_limit(issuer_id, action).is_none() {
+            return;
+        }
+
+        let mut state = self.action_quotas.lock();
+        state.roll_over();
+        *state.counts.entry((issuer_id, action)).or_default() += 1;
+    }
+
     pub async fn admin_edit_roles(
         &self,
         issuer_id: i32,
@@ -621,6 +962,10 @@ impl UsersModule {
     ) -> Result<(), Error> {
         self.punishment_preconditions(issuer_id, account_id).await?;
 
+        if !self.check_action_quota(issuer_id, QuotaAction::EditRoles) {
+            return Err(Error::QuotaExceeded);
+        }
+
         // disallow adding roles higher than your highest
         let highest_p = self.get_user_highest_priority(issuer_id).await?;
 
@@ -634,6 +979,7 @@ impl UsersModule {
 
         let rolediff = self.compute_role_diff(account_id, new_roles).await?;
         self.inner_set_roles(account_id, new_roles).await?;
+        self.record_quota_action(issuer_id, QuotaAction::EditRoles);
 
         // log to db and discord
         self.perform_log(
@@ -673,16 +1019,31 @@ impl UsersModule {
             return Err(Error::NotFound);
         }
 
+        self.invalidate_cached_user(account_id);
+
         Ok(())
     }
 
+    /// Removes all roles that come from Discord, and the whitelist if it was granted by Discord membership
     pub async fn system_clear_linked_roles(&self, account_id: i32) -> Result<(), Error> {
         let user = self.get_user(account_id).await?.ok_or(Error::NotFound)?;
+
+        if user.whitelisted_by_discord {
+            info!("Revoking whitelist granted by Discord membership from {account_id}");
+            self.db.set_discord_whitelisted(account_id, false).await?;
+            self.invalidate_cached_user(account_id);
+        }
+
         let role = self.compute_from_user(&user);
         let mut role_ids = role.roles.clone();
 
         // keep roles that do not have discord_id set, also remove invalid roles
-        role_ids.retain(|id| self.roles.get(*id as usize).is_some_and(|r| r.discord_id == 0));
+        // and the role granted for linking
+        let link_role = self.link_role_id();
+        role_ids.retain(|id| {
+            Some(*id) != link_role
+                && self.roles.get(*id as usize).is_some_and(|r| r.discord_id == 0)
+        });
 
         self.system_set_roles(account_id, &role_ids).await
     }
@@ -731,6 +1092,7 @@ impl UsersModule {
         password: &str,
     ) -> DatabaseResult<()> {
         self.db.set_admin_password_hash(account_id, &pwhash::hash(password)).await?;
+        self.invalidate_cached_user(account_id);
         self.perform_log(issuer_id, LogAction::EditPassword { account_id }).await;
 
         Ok(())
@@ -745,7 +1107,57 @@ impl UsersModule {
         color2: u16,
         glow_color: u16,
     ) -> DatabaseResult<()> {
-        self.db.update_user(account_id, username, cube, color1, color2, glow_color).await
+        self.db.update_user(account_id, username, cube, color1, color2, glow_color).await?;
+        self.invalidate_cached_user(account_id);
+
+        Ok(())
+    }
+
+    /// Creates a database entry for a user that does not have one, see `create_users_on_login`.
+    /// Returns `true` only if the user is actually new: they had no entry and no recorded uident,
+    /// and entries have been created on login for at least `welcome_grace_days`,
+    /// so that players who were around before the switch was turned on are not greeted as new.
+    pub async fn create_user_on_login(
+        &self,
+        account_id: i32,
+        username: &str,
+        icons: &PlayerIconData,
+    ) -> DatabaseResult<bool> {
+        let had_uident = self.db.get_user_uident(account_id).await?.is_some();
+
+        let inserted = self
+            .db
+            .insert_first_login(
+                account_id,
+                username,
+                icons.cube,
+                icons.color1,
+                icons.color2,
+                icons.glow_color,
+            )
+            .await?;
+
+        if !inserted || had_uident {
+            return Ok(false);
+        }
+
+        let cached = *self.first_login_tracking_since.lock();
+        let since = match cached {
+            Some(since) => since,
+            None => {
+                let Some(since) = self.db.oldest_first_seen().await? else {
+                    return Ok(false);
+                };
+
+                *self.first_login_tracking_since.lock() = Some(since);
+                since
+            }
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let grace = self.config().welcome_grace_days as i64 * 86400;
+
+        Ok(now - since >= grace)
     }
 
     pub async fn admin_set_whitelisted(
@@ -754,13 +1166,29 @@ impl UsersModule {
         account_id: i32,
         whitelisted: bool,
     ) -> DatabaseResult<()> {
-        self.db.set_whitelisted(account_id, whitelisted).await
+        self.db.set_whitelisted(account_id, whitelisted).await?;
+        self.invalidate_cached_user(account_id);
+
+        Ok(())
+    }
+
+    pub async fn admin_set_note(
+        &self,
+        issuer_id: i32,
+        account_id: i32,
+        note: &str,
+    ) -> DatabaseResult<()> {
+        self.db.set_note(account_id, note).await?;
+        self.invalidate_cached_user(account_id);
+        info!("{issuer_id} updated the note of {account_id}: \"{note}\"");
+
+        Ok(())
     }
 
     pub async fn fetch_moderators(&self) -> DatabaseResult<Vec<FetchedMod>> {
         let mut out = Vec::new();
 
-        let mut users = self.db.fetch_all_with_roles().await?;
+        let mut users = self.db.fetch_all_with_any_role(&self.staff_role_ids).await?;
 
         users.retain(|user| {
             let role = self
@@ -785,6 +1213,24 @@ impl UsersModule {
         Ok(out)
     }
 
+    /// Like `fetch_moderators`, but the result is cached for 5 minutes
+    pub async fn fetch_moderators_cached(&self) -> DatabaseResult<Vec<FetchedMod>> {
+        const TTL: Duration = Duration::from_mins(5);
+
+        let now = Instant::now();
+
+        if let Some((expiry, mods)) = &*self.moderators_cache.read().await
+            && now < *expiry
+        {
+            return Ok(mods.clone());
+        }
+
+        let mods = self.fetch_moderators().await?;
+        *self.moderators_cache.write().await = Some((now + TTL, mods.clone()));
+
+        Ok(mods)
+    }
+
     async fn get_user_highest_priority(&self, account_id: i32) -> DatabaseResult<i32> {
         let config = self.config();
         if config.super_admins.contains(&account_id) {
@@ -853,9 +1299,20 @@ impl UsersModule {
     ) -> Result<(), PunishUserError> {
         self.punishment_preconditions(issuer_id, account_id).await?;
 
+        if !self.check_action_quota(issuer_id, r#type.into()) {
+            return Err(PunishUserError::QuotaExceeded);
+        }
+
         let exp = NonZeroI64::new(expires_at);
         match self.db.punish_user(issuer_id, account_id, r#type, reason, exp).await? {
             Some(edit) => {
+                self.invalidate_cached_user(account_id);
+
+                // editing an existing punishment does not count as a new one
+                if !edit {
+                    self.record_quota_action(issuer_id, r#type.into());
+                }
+
                 self.perform_log(
                     issuer_id,
                     self.log_for_punish(account_id, reason, expires_at, r#type, edit),
@@ -883,11 +1340,51 @@ impl UsersModule {
             return Err(PunishUserError::NotPunished);
         }
 
+        self.invalidate_cached_user(account_id);
+
         self.perform_log(issuer_id, self.log_for_unpunish(account_id, r#type)).await;
 
         Ok(())
     }
 
+    /// Returns users who still have an active punishment of the given type that was issued by
+    /// `issuer` within the time window, according to the audit log
+    pub async fn find_punished_by(
+        &self,
+        issuer: i32,
+        r#type: UserPunishmentType,
+        after: i64,
+        before: i64,
+    ) -> DatabaseResult<Vec<DbUser>> {
+        let type_str = match r#type {
+            UserPunishmentType::Ban => "ban",
+            UserPunishmentType::Mute => "mute",
+            UserPunishmentType::RoomBan => "roomban",
+        };
+
+        let targets = self.db.fetch_action_targets(issuer, type_str, after, before).await?;
+        let mut out = Vec::new();
+
+        for id in targets {
+            let Some(user) = self.db.get_user(id).await? else {
+                continue;
+            };
+
+            let active = match r#type {
+                UserPunishmentType::Ban => user.active_ban.as_ref(),
+                UserPunishmentType::Mute => user.active_mute.as_ref(),
+                UserPunishmentType::RoomBan => user.active_room_ban.as_ref(),
+            };
+
+            // skip users that were unpunished since, or punished again by someone else
+            if active.is_some_and(|p| p.issued_by == issuer) {
+                out.push(user);
+            }
+        }
+
+        Ok(out)
+    }
+
     pub async fn admin_fetch_logs(
         &self,
         issuer: i32,
@@ -954,6 +1451,10 @@ impl UsersModule {
         self.perform_log(issuer_id, LogAction::Kick { account_id, username, reason }).await
     }
 
+    pub async fn log_close_room(&self, issuer_id: i32, room_id: u32, room_name: &str, owner: i32) {
+        self.perform_log(issuer_id, LogAction::CloseRoom { room_id, room_name, owner }).await
+    }
+
     pub async fn log_notice(&self, issuer_id: i32, account_id: i32, message: &str) {
         self.perform_log(issuer_id, LogAction::Notice { account_id, message }).await
     }
@@ -1024,6 +1525,15 @@ impl UsersModule {
             warn!("Failed to log punishment in database: {e}");
         }
 
+        #[cfg(feature = "analytics")]
+        if let Some(analytics) = self.server().handler().opt_module::<AnalyticsModule>() {
+            analytics.log_moderation_event(ModerationEvent::new(
+                log.type_str(),
+                issuer_id,
+                log.account_id(),
+            ));
+        }
+
         #[cfg(feature = "discord")]
         {
             let config = self.config();
@@ -1202,6 +1712,17 @@ impl UsersModule {
             LogAction::EditPassword { .. } => {
                 // not logged
             }
+
+            LogAction::CloseRoom { room_id, room_name, .. } => {
+                msg = msg.add_embed(
+                    CreateEmbed::new()
+                        .title("Room closed")
+                        .color(hex_color_to_decimal("#e8d34d"))
+                        .description(format!("{room_name} ({room_id})"))
+                        .author(CreateEmbedAuthor::new(format!("Owner: {target_combo}")))
+                        .field("Performed by", issuer_combo, true),
+                )
+            }
         }
 
         Ok(msg)
@@ -1232,6 +1753,31 @@ impl UsersModule {
         self.blacklisted_authors.load().contains(&author_id)
     }
 
+    /// Whether the server started without a database connection and is still waiting for one
+    pub fn is_degraded(&self) -> bool {
+        !self.db.is_connected()
+    }
+
+    /// Attempts to connect to the database if it was unreachable on startup
+    async fn try_reconnect(&self) {
+        if !self.is_degraded() {
+            return;
+        }
+
+        let config = self.config();
+
+        if let Err(e) = self.db.reconnect(&config.database_url, config.database_pool_size).await {
+            warn!("Users database is still unreachable: {e}");
+            return;
+        }
+
+        info!("Connected to the users database, leaving degraded mode");
+
+        if let Err(e) = self.refresh_blacklist_cache().await {
+            error!("Failed to refresh blacklist cache: {e}");
+        }
+    }
+
     pub async fn refresh_blacklist_cache(&self) -> DatabaseResult<()> {
         let levels = self.db.fetch_blacklisted_levels().await?.into_iter().collect();
         let authors = self.db.fetch_blacklisted_authors().await?.into_iter().collect();
@@ -1242,6 +1788,88 @@ impl UsersModule {
         Ok(())
     }
 
+    /// Queues the user's `last_seen` timestamp to be updated on the next flush
+    pub fn mark_seen(&self, account_id: i32) {
+        self.pending_last_seen.lock().insert(account_id);
+    }
+
+    /// Writes `last_seen` for all connected users and those who disconnected since the last flush
+    pub async fn flush_last_seen(&self) {
+        let mut ids: Vec<i32> =
+            std::mem::take(&mut *self.pending_last_seen.lock()).into_iter().collect();
+
+        if let Some(server) = self.server.get().and_then(|s| s.upgrade()) {
+            let clients = server.handler().get_all_authorized_clients();
+            ids.extend(clients.iter().map(|c| c.account_id()));
+        }
+
+        if ids.is_empty() {
+            return;
+        }
+
+        trace!("Updating last seen time of {} users", ids.len());
+
+        if let Err(e) = self.db.update_last_seen(&ids).await {
+            error!("Failed to update last seen times: {e}");
+        }
+    }
+
+    /// Removes the configured staff roles from users that have been inactive for too long
+    async fn demote_inactive_staff(&self) -> Result<(), Error> {
+        let config = self.config();
+        if config.inactive_staff_days == 0 || config.inactive_staff_roles.is_empty() {
+            return Ok(());
+        }
+
+        let demoted_roles = self.role_str_to_ids(&config.inactive_staff_roles.join(","));
+        if demoted_roles.is_empty() {
+            return Ok(());
+        }
+
+        let cutoff = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_sub(Duration::from_days(config.inactive_staff_days as u64))
+            .as_secs() as i64;
+
+        for user in self.db.fetch_all_with_roles().await? {
+            let account_id = user.account_id as i32;
+
+            // users that have not been seen since last_seen tracking was added are skipped
+            if config.super_admins.contains(&account_id)
+                || user.last_seen.is_none_or(|t| t >= cutoff)
+            {
+                continue;
+            }
+
+            let roles = self.role_str_to_ids(user.roles.as_deref().unwrap_or_default());
+            let kept: Vec<u8> =
+                roles.iter().copied().filter(|id| !demoted_roles.contains(id)).collect();
+
+            if kept.len() == roles.len() {
+                continue;
+            }
+
+            let username = user.username.as_deref().unwrap_or("Unknown");
+            info!(
+                "Demoting inactive staff member {username} ({account_id}), last seen at {}",
+                user.last_seen.unwrap_or_default()
+            );
+
+            self.system_set_roles(account_id, &kept).await?;
+
+            #[cfg(feature = "discord")]
+            if let Some(d) = self.discord.as_ref() {
+                d.send_alert(DiscordMessage::new().content(format!(
+                    "Removed staff roles from {username} ({account_id}) due to inactivity, last seen <t:{}:R>",
+                    user.last_seen.unwrap_or_default()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     async fn record_player_count(&self, count: u32) -> DatabaseResult<()> {
         trace!("Recording player count: {count}");
         self.db.record_player_count(count).await?;
@@ -1328,10 +1956,23 @@ impl UsersModule {
 
 impl ServerModule for UsersModule {
     async fn new(config: Arc<Config>, handler: &ConnectionHandler) -> ModuleInitResult<Self> {
-        let db = UsersDb::new(&config.database_url, config.database_pool_size).await?;
+        let db = match UsersDb::new(&config.database_url, config.database_pool_size).await {
+            Ok(db) => {
+                db.run_migrations().await?;
+                db.truncate_unimportant_logs(Duration::from_days(180)).await?;
+                db
+            }
 
-        db.run_migrations().await?;
-        db.truncate_unimportant_logs(Duration::from_days(180)).await?;
+            Err(e) if !config.db_required => {
+                error!("Failed to connect to the users database: {e}");
+                error!(
+                    "!!! Starting in degraded mode, moderation and persistence are unavailable until the database is reachable !!!"
+                );
+                UsersDb::disconnected()
+            }
+
+            Err(e) => return Err(e.into()),
+        };
 
         let mut roles = Vec::new();
         for role in config.roles.iter() {
@@ -1360,8 +2001,14 @@ impl ServerModule for UsersModule {
         let _ = handler;
 
         // load blacklisted levels and authors
-        let levels = db.fetch_blacklisted_levels().await?.into_iter().collect();
-        let authors = db.fetch_blacklisted_authors().await?.into_iter().collect();
+        let (levels, authors) = if db.is_connected() {
+            (
+                db.fetch_blacklisted_levels().await?.into_iter().collect(),
+                db.fetch_blacklisted_authors().await?.into_iter().collect(),
+            )
+        } else {
+            Default::default()
+        };
 
         #[cfg(feature = "web")]
         {
@@ -1376,11 +2023,22 @@ impl ServerModule for UsersModule {
             .await;
 
             web.add_route("/players/{id}", axum::routing::get(get_user_handler)).await;
+            web.add_route(
+                "/moderators",
+                axum::routing::get(moderators_handler)
+                    .layer(CorsLayer::new().allow_methods(Any).allow_origin(Any)),
+            )
+            .await;
         }
 
         Ok(Self {
             db,
             server: OnceLock::new(),
+            staff_role_ids: roles
+                .iter()
+                .filter(|r| r.can_moderate())
+                .map(|r| r.id.clone())
+                .collect(),
             roles,
             config: ArcSwap::new(config.clone()),
             gd_client: GDApiClient::new(handler.http_client()),
@@ -1390,19 +2048,43 @@ impl ServerModule for UsersModule {
             discord_role_map,
             blacklisted_authors: ArcSwap::new(Arc::new(authors)),
             blacklisted_levels: ArcSwap::new(Arc::new(levels)),
+            trusted_accounts: ArcSwap::new(Arc::new(
+                config.trusted_accounts.iter().copied().collect(),
+            )),
             player_counts_cache: RwLock::new(HashMap::new()),
+            moderators_cache: RwLock::new(None),
+            pending_last_seen: parking_lot::Mutex::new(FxHashSet::default()),
+            admin_user_cache: parking_lot::Mutex::new(FxHashMap::default()),
+            action_quotas: parking_lot::Mutex::new(ActionQuotaState::default()),
+            first_login_tracking_since: parking_lot::Mutex::new(None),
         })
     }
 
     fn on_launch(&self, server: &ServerHandle<ConnectionHandler>) {
         let _ = self.server.set(server.make_weak());
 
+        if self.is_degraded() {
+            server.schedule(Duration::from_secs(30), async move |server| {
+                server.handler().module::<Self>().try_reconnect().await;
+            });
+        }
+
         server.schedule(Duration::from_hours(12), async move |server| {
             if let Err(e) = server.handler().module::<Self>().refresh_blacklist_cache().await {
                 error!("Failed to refresh blacklist cache: {e}");
             }
         });
 
+        server.schedule(Duration::from_mins(5), async move |server| {
+            server.handler().module::<Self>().flush_last_seen().await;
+        });
+
+        server.schedule(Duration::from_hours(6), async move |server| {
+            if let Err(e) = server.handler().module::<Self>().demote_inactive_staff().await {
+                error!("Failed to demote inactive staff: {e}");
+            }
+        });
+
         if self.config().record_player_counts {
             server.schedule(Duration::from_mins(1), async move |server| {
                 let me = server.handler().module::<Self>();
@@ -1415,6 +2097,20 @@ impl ServerModule for UsersModule {
     }
 
     fn reload(&self, _server: &ServerHandle<ConnectionHandler>, config: Arc<Config>) {
+        let old = self.config.load();
+
+        // these are only read on startup
+        if old.database_url != config.database_url
+            || old.database_pool_size != config.database_pool_size
+            || old.roles.len() != config.roles.len()
+            || old.roles.iter().zip(config.roles.iter()).any(|(a, b)| a.id != b.id)
+        {
+            warn!(
+                "Database or role settings were changed, the server must be restarted to apply them"
+            );
+        }
+
+        self.trusted_accounts.store(Arc::new(config.trusted_accounts.iter().copied().collect()));
         self.config.store(config);
     }
 
@@ -1479,11 +2175,31 @@ async fn player_count_handler(
     }
 }
 
+#[cfg(feature = "web")]
+async fn moderators_handler(State(state): State<Arc<WebState>>) -> impl IntoResponse {
+    let server = state.server();
+    let users = server.handler().module::<UsersModule>();
+
+    match users.fetch_moderators_cached().await {
+        Ok(mods) => {
+            (StatusCode::OK, serde_json::to_string(&mods).unwrap_or_else(|_| "[]".to_string()))
+        }
+
+        Err(e) => {
+            warn!("Failed to fetch moderators: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "".to_owned())
+        }
+    }
+}
+
 #[derive(serde::Serialize, Clone)]
 pub struct GetUserResponse {
     pub account_id: i32,
     pub username: String,
     pub roles: Vec<String>,
+    /// Unix timestamp of when the user was last online
+    pub last_seen: Option<i64>,
+    pub first_seen: Option<i64>,
 }
 
 #[cfg(feature = "web")]
@@ -1509,6 +2225,8 @@ async fn get_user_handler(
                 account_id: u.account_id,
                 username: u.username().to_owned(),
                 roles,
+                last_seen: u.last_seen.map(|x| x.get()),
+                first_seen: u.first_seen.map(|x| x.get()),
             };
             (StatusCode::OK, serde_json::to_string(&resp).unwrap())
         }
@@ -1,18 +1,50 @@
 use serde::{Deserialize, Serialize};
 
-fn default_port() -> u16 {
-    8080
+fn default_address() -> String {
+    "127.0.0.1:8080".into()
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
-    #[serde(default = "default_port")]
-    pub port: u16,
+    /// Address the HTTP server binds to, separate from the game listeners. By default only
+    /// reachable locally, so internal endpoints like `/status` and `/metrics` stay off the public
+    /// interface. Player-facing routes (`/players`, Discord OAuth callbacks) are meant to be public,
+    /// so either bind this to a public address and protect the internal routes with tokens below,
+    /// or expose only those routes through a reverse proxy.
+    ///
+    /// This is `address` in the web config rather than `core.http_address`, because the HTTP
+    /// server only exists with the `web` feature and all of its settings live here.
+    #[serde(default = "default_address")]
+    pub address: String,
+    /// Deprecated, use `address` instead. If set, overrides it and binds to `0.0.0.0:<port>`,
+    /// which exposes every route on all interfaces. A warning is logged on startup when set.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// If set, requests to `/status` must include an `Authorization: Bearer <token>` header
+    #[serde(default)]
+    pub status_token: Option<String>,
+    /// If set, requests to `/metrics` must include an `Authorization: Bearer <token>` header
+    #[serde(default)]
+    pub metrics_token: Option<String>,
+}
+
+impl Config {
+    pub fn bind_address(&self) -> String {
+        match self.port {
+            Some(port) => format!("0.0.0.0:{port}"),
+            None => self.address.clone(),
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { port: default_port() }
+        Self {
+            address: default_address(),
+            port: None,
+            status_token: None,
+            metrics_token: None,
+        }
     }
 }
@@ -1,15 +1,24 @@
-use std::sync::{Arc, OnceLock};
+use std::{
+    fmt::Write,
+    sync::{Arc, OnceLock},
+};
 
 use anyhow::anyhow;
 use arc_swap::ArcSwap;
-use axum::{extract::State, response::IntoResponse, routing::MethodRouter};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+};
 use server_shared::qunet::server::{ServerHandle, WeakServerHandle};
 use tokio::{net::TcpListener, sync::Mutex};
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::core::{
     handler::ConnectionHandler,
     module::{ConfigurableModule, ModuleInitResult, ServerModule},
+    util::constant_time_eq,
 };
 use config::Config;
 
@@ -17,6 +26,7 @@ mod config;
 
 pub struct WebState {
     server: OnceLock<WeakServerHandle<ConnectionHandler>>,
+    config: Arc<Config>,
 }
 
 impl WebState {
@@ -27,6 +37,19 @@ impl WebState {
             .upgrade()
             .expect("web server handle was dropped")
     }
+
+    /// Returns whether the request is allowed to access a route protected by the given token
+    fn is_authorized(&self, headers: &HeaderMap, token: &Option<String>) -> bool {
+        let Some(token) = token.as_deref().filter(|t| !t.is_empty()) else {
+            return true;
+        };
+
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|v| constant_time_eq(v, token))
+    }
 }
 
 pub struct WebModule {
@@ -53,11 +76,18 @@ impl WebModule {
 
 impl ServerModule for WebModule {
     async fn new(config: Arc<Config>, _handler: &ConnectionHandler) -> ModuleInitResult<Self> {
-        let listener = TcpListener::bind(format!("0.0.0.0:{}", config.port))
+        if config.port.is_some() {
+            warn!("web.port is deprecated and binds to all interfaces, use web.address instead");
+        }
+
+        let listener = TcpListener::bind(config.bind_address())
             .await
-            .map_err(|e| anyhow!("failed to bind web server port: {e}"))?;
+            .map_err(|e| anyhow!("failed to bind web server address: {e}"))?;
 
-        let state = Arc::new(WebState { server: OnceLock::new() });
+        let state = Arc::new(WebState {
+            server: OnceLock::new(),
+            config: config.clone(),
+        });
         let router = axum::Router::new();
 
         Ok(Self {
@@ -82,6 +112,7 @@ impl ServerModule for WebModule {
 
         tokio::spawn(async move {
             this.add_route("/status", axum::routing::get(status_handler)).await;
+            this.add_route("/metrics", axum::routing::get(metrics_handler)).await;
 
             let listener = this.listener.lock().await.take().unwrap();
             let router = this.router.lock().await.take().unwrap().with_state(this.state.clone());
@@ -96,8 +127,48 @@ impl ConfigurableModule for WebModule {
     type Config = Config;
 }
 
-async fn status_handler(State(wstate): State<Arc<WebState>>) -> impl IntoResponse {
+async fn status_handler(State(wstate): State<Arc<WebState>>, headers: HeaderMap) -> Response {
+    if !wstate.is_authorized(&headers, &wstate.config.status_token) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
     let server = wstate.server();
     let health = server.handler().get_server_health();
-    axum::Json(health)
+    axum::Json(health).into_response()
+}
+
+/// Same data as `/status`, in the Prometheus text format
+async fn metrics_handler(State(wstate): State<Arc<WebState>>, headers: HeaderMap) -> Response {
+    if !wstate.is_authorized(&headers, &wstate.config.metrics_token) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let server = wstate.server();
+    let health = server.handler().get_server_health();
+
+    let up = |state: &str| u8::from(state == "up");
+
+    let mut out = String::new();
+    let _ = writeln!(out, "globed_uptime_seconds {}", health.uptime);
+    let _ = writeln!(out, "globed_clients {}", health.clients);
+    let _ = writeln!(out, "globed_rooms {}", health.rooms);
+    let _ = writeln!(out, "globed_levels {}", health.levels);
+    let _ = writeln!(out, "globed_argon_up {}", up(health.argon_state));
+    let _ = writeln!(out, "globed_analytics_up {}", up(health.analytics_state));
+    let _ =
+        writeln!(out, "globed_analytics_dropped_events_total {}", health.analytics_dropped_events);
+
+    for (reason, count) in &health.login_failures {
+        let _ = writeln!(out, "globed_login_failures_total{{reason=\"{reason}\"}} {count}");
+    }
+
+    for (site, count) in &health.encode_failures {
+        let _ = writeln!(out, "globed_encode_failures_total{{site=\"{site}\"}} {count}");
+    }
+
+    for gs in &health.game_servers {
+        let _ = writeln!(out, "globed_game_server_load{{id=\"{}\"}} {}", gs.id, gs.load);
+    }
+
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], out).into_response()
 }
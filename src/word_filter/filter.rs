@@ -1,33 +1,70 @@
 use std::{collections::HashSet, path::Path};
 
 use aho_corasick::AhoCorasick;
+use regex::{Regex, RegexSet, RegexSetBuilder};
+use tracing::warn;
 
-use crate::word_filter::word_iterator::WordIterator;
+use crate::word_filter::{
+    normalize::{Normalization, normalize},
+    word_iterator::WordIterator,
+};
 
 pub struct WordFilter {
     algo: AhoCorasick,
     word_count: usize,
     words: Vec<String>,
     whole_words: HashSet<String>,
+    patterns: Option<RegexSet>,
+    normalization: Normalization,
 }
 
 impl WordFilter {
-    pub fn new(words: Vec<String>, whole_words: HashSet<String>) -> Self {
+    pub fn new(words: Vec<String>, whole_words: HashSet<String>, patterns: Vec<String>) -> Self {
+        let patterns = if patterns.is_empty() {
+            None
+        } else {
+            match RegexSetBuilder::new(&patterns).case_insensitive(true).build() {
+                Ok(set) => Some(set),
+                Err(e) => {
+                    warn!("Failed to compile word filter patterns: {e}");
+                    None
+                }
+            }
+        };
+
         Self {
-            word_count: words.len() + whole_words.len(),
+            word_count: words.len() + whole_words.len() + patterns.as_ref().map_or(0, |p| p.len()),
             algo: AhoCorasick::builder()
                 .ascii_case_insensitive(true)
                 .build(&words)
                 .expect("failed to create word filter"),
             whole_words,
             words,
+            patterns,
+            normalization: Normalization::Off,
         }
     }
 
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
     pub fn new_from_lines(mut words: Vec<String>) -> Self {
         let mut whole_words = HashSet::new();
+        let mut patterns = Vec::new();
 
         words.retain_mut(|w| {
+            if let Some(pattern) = w.strip_prefix("re:") {
+                // validate each pattern separately, so one bad entry doesn't break the whole set
+                match Regex::new(pattern) {
+                    Ok(_) => patterns.push(pattern.to_owned()),
+                    Err(e) => warn!("Skipping invalid word filter pattern '{pattern}': {e}"),
+                }
+
+                return false;
+            }
+
             let is_whole = w.starts_with("!!") && w.ends_with("!!") && w.len() > 4;
 
             if is_whole {
@@ -40,7 +77,7 @@ impl WordFilter {
             !is_whole && !w.is_empty()
         });
 
-        Self::new(words, whole_words)
+        Self::new(words, whole_words, patterns)
     }
 
     pub async fn new_from_path(p: &Path) -> Result<Self, std::io::Error> {
@@ -51,6 +88,15 @@ impl WordFilter {
     }
 
     pub fn is_bad(&self, content: &str) -> Option<&str> {
+        // ascii input has nothing to normalize, skip the allocation
+        if self.normalization == Normalization::Off || content.is_ascii() {
+            return self.find_bad(content);
+        }
+
+        self.find_bad(&normalize(content, self.normalization))
+    }
+
+    fn find_bad(&self, content: &str) -> Option<&str> {
         if let Some(x) = self.algo.find(content) {
             return Some(
                 self.words.get(x.pattern().as_usize()).map_or("<unknown>", |x| x.as_str()),
@@ -79,6 +125,13 @@ impl WordFilter {
             }
         }
 
+        // patterns are the slowest, so they are checked last
+        if let Some(patterns) = &self.patterns
+            && let Some(idx) = patterns.matches(content).iter().next()
+        {
+            return Some(&patterns.patterns()[idx]);
+        }
+
         None
     }
 
@@ -89,6 +142,6 @@ impl WordFilter {
 
 impl Default for WordFilter {
     fn default() -> Self {
-        Self::new(Vec::new(), HashSet::new())
+        Self::new(Vec::new(), HashSet::new(), Vec::new())
     }
 }
@@ -2,6 +2,7 @@ use std::{path::PathBuf, sync::Arc};
 
 use arc_swap::ArcSwap;
 use filter::WordFilter;
+use normalize::Normalization;
 use serde::{Deserialize, Serialize};
 use server_shared::qunet::server::ServerHandle;
 use tracing::{info, warn};
@@ -12,19 +13,22 @@ use crate::core::{
 };
 
 mod filter;
+mod normalize;
 mod word_iterator;
 
 pub struct WordFilterModule {
+    config: ArcSwap<Config>,
     filter: ArcSwap<Option<WordFilter>>,
 }
 
 impl ServerModule for WordFilterModule {
     async fn new(config: Arc<Config>, _handler: &ConnectionHandler) -> ModuleInitResult<Self> {
         let this = Self {
+            config: ArcSwap::new(config.clone()),
             filter: ArcSwap::new(Arc::new(None)),
         };
 
-        this.do_reload(&config).await;
+        let _ = this.do_reload(&config).await;
 
         Ok(this)
     }
@@ -38,10 +42,12 @@ impl ServerModule for WordFilterModule {
     }
 
     fn reload(&self, server: &ServerHandle<ConnectionHandler>, config: Arc<Config>) {
+        self.config.store(config.clone());
+
         let this = server.handler().opt_module_owned::<Self>().unwrap();
 
         tokio::spawn(async move {
-            this.do_reload(&config).await;
+            let _ = this.do_reload(&config).await;
         });
     }
 }
@@ -56,12 +62,21 @@ impl WordFilterModule {
         (**filter).as_ref().and_then(|wf| wf.is_bad(content).map(|x| x.to_owned()))
     }
 
-    pub async fn do_reload(&self, config: &Config) {
+    /// Reloads the word filter from the currently configured file, returns the new word count.
+    /// On failure, the previously loaded filter is kept.
+    pub async fn reload_from_file(&self) -> std::io::Result<usize> {
+        let config = self.config.load_full();
+        self.do_reload(&config).await
+    }
+
+    pub async fn do_reload(&self, config: &Config) -> std::io::Result<usize> {
         let path = config.file_path.clone().unwrap_or_else(|| "config/word-filter.txt".into());
 
         let filter = if path.exists() {
-            let filter =
-                WordFilter::new_from_path(&path).await.expect("Failed to create word filter");
+            let filter = WordFilter::new_from_path(&path)
+                .await
+                .inspect_err(|e| warn!("Failed to load the word filter from {path:?}: {e}"))?
+                .with_normalization(config.normalization);
             info!("Loaded word filter from {path:?} with {} words", filter.word_count());
             Some(filter)
         } else {
@@ -70,12 +85,17 @@ impl WordFilterModule {
                     "Failed to load the word filter from {:?}, file does not exist",
                     config.file_path
                 );
+
+                return Err(std::io::ErrorKind::NotFound.into());
             }
 
             None
         };
 
+        let word_count = filter.as_ref().map_or(0, |f| f.word_count());
         self.filter.store(Arc::new(filter));
+
+        Ok(word_count)
     }
 }
 
@@ -87,4 +107,7 @@ pub struct Config {
     /// Now unused
     #[serde(default)]
     watch: bool,
+    /// How aggressively input is normalized before checking: "off", "basic" or "aggressive"
+    #[serde(default)]
+    normalization: Normalization,
 }
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Input is checked as-is
+    #[default]
+    Off,
+    /// Compatibility decomposition (NFKD) with diacritics stripped, e.g. "bàdwörd" -> "badword"
+    Basic,
+    /// Same as basic, additionally folds common homoglyphs (Cyrillic, Greek) into latin letters.
+    /// This catches more bypasses but can cause false positives for non-latin text.
+    Aggressive,
+}
+
+pub fn normalize(input: &str, mode: Normalization) -> String {
+    let chars = input.nfkd().filter(|c| !is_combining_mark(*c));
+
+    match mode {
+        Normalization::Off => input.to_owned(),
+        Normalization::Basic => chars.collect(),
+        Normalization::Aggressive => chars.map(fold_homoglyph).collect(),
+    }
+}
+
+fn fold_homoglyph(c: char) -> char {
+    match c {
+        // cyrillic
+        'а' => 'a',
+        'в' => 'b',
+        'е' | 'ё' => 'e',
+        'к' => 'k',
+        'м' => 'm',
+        'н' => 'h',
+        'о' => 'o',
+        'р' => 'p',
+        'с' => 'c',
+        'т' => 't',
+        'у' => 'y',
+        'х' => 'x',
+        'і' => 'i',
+        'ј' => 'j',
+        'ѕ' => 's',
+        'А' => 'A',
+        'В' => 'B',
+        'Е' | 'Ё' => 'E',
+        'К' => 'K',
+        'М' => 'M',
+        'Н' => 'H',
+        'О' => 'O',
+        'Р' => 'P',
+        'С' => 'C',
+        'Т' => 'T',
+        'У' => 'Y',
+        'Х' => 'X',
+        'І' => 'I',
+        'Ј' => 'J',
+        'Ѕ' => 'S',
+
+        // greek
+        'α' => 'a',
+        'β' => 'b',
+        'ε' => 'e',
+        'ι' => 'i',
+        'κ' => 'k',
+        'ν' => 'v',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'τ' => 't',
+        'υ' => 'u',
+        'χ' => 'x',
+        'Α' => 'A',
+        'Β' => 'B',
+        'Ε' => 'E',
+        'Η' => 'H',
+        'Ι' => 'I',
+        'Κ' => 'K',
+        'Μ' => 'M',
+        'Ν' => 'N',
+        'Ο' => 'O',
+        'Ρ' => 'P',
+        'Τ' => 'T',
+        'Υ' => 'Y',
+        'Χ' => 'X',
+        'Ζ' => 'Z',
+
+        c => c,
+    }
+}